@@ -39,72 +39,319 @@ For example:
 What is the position of the character that causes Santa to first enter the basement?
 */
 
+use std::collections::{BTreeMap, HashMap};
+use std::env;
 use std::error::Error;
-use std::fs;
-use std::io::{self, Write};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
 use std::result::Result as StdResult;
 
 type Result<T> = StdResult<T, Box<dyn Error>>;
 
+/// How many bytes of instructions are read at a time.
+const CHUNK_SIZE: usize = 64 * 1024;
+
 enum Move {
     UpFloor,
     DownFloor,
 }
 
-impl TryFrom<char> for Move {
+impl TryFrom<u8> for Move {
     type Error = &'static str;
 
-    fn try_from(value: char) -> StdResult<Self, Self::Error> {
+    fn try_from(value: u8) -> StdResult<Self, Self::Error> {
         match value {
-            '(' => Ok(Move::UpFloor),
-            ')' => Ok(Move::DownFloor),
+            b'(' => Ok(Move::UpFloor),
+            b')' => Ok(Move::DownFloor),
             _ => Err("invalid move!"),
         }
     }
 }
 
-struct Santa {
-    moves: Vec<Move>,
-    current_floor: i32,
+/// Santa's walk through the building, read from the instructions as he goes.
+///
+/// Iterating yields the floor he is on after each move. Only one chunk of
+/// the instructions is held in memory at a time, and any other bytes are skipped.
+struct Santa<R> {
+    moves: R,
+    chunk: Box<[u8]>,
+    pos: usize,
+    len: usize,
+    current_floor: i64,
 }
 
-impl Santa {
-    fn new(moves: &str) -> Self {
+impl<R: Read> Santa<R> {
+    fn new(moves: R) -> Self {
         Self {
+            moves,
+            chunk: vec![0; CHUNK_SIZE].into_boxed_slice(),
+            pos: 0,
+            len: 0,
             current_floor: 0,
-            moves: moves
-                .chars()
-                .filter_map(|c| c.try_into().ok())
-                .collect::<Vec<Move>>(),
         }
     }
 
     /// On which floor does Santa end up after all moves?
     /// And how many instuctions does it take for Santa to enter the basement?
-    fn follow_instructions(mut self) -> (i32, Option<usize>) {
+    fn follow_instructions(mut self) -> io::Result<(i64, Option<usize>)> {
         let mut basement_instruction_pos = None;
 
-        for (idx, mv) in self.moves.iter().enumerate() {
-            match mv {
-                Move::UpFloor => self.current_floor += 1,
-                Move::DownFloor => self.current_floor -= 1,
+        for (idx, floor) in self.by_ref().enumerate() {
+            let floor = floor?;
+            if basement_instruction_pos.is_none() && floor == -1 {
+                basement_instruction_pos = Some(idx + 1);
             }
+        }
 
-            if basement_instruction_pos.is_none() && self.current_floor == -1 {
-                basement_instruction_pos = Some(idx + 1);
+        Ok((self.current_floor, basement_instruction_pos))
+    }
+
+    /// Everything about the walk, gathered in a single pass.
+    fn history(self) -> io::Result<History> {
+        let mut history = History {
+            moves: 0,
+            final_floor: 0,
+            highest: (0, 0),
+            lowest: (0, 0),
+            basement_events: vec![],
+            time_on_floor: BTreeMap::from([(0, 1)]),
+            ground_revisits: 0,
+        };
+
+        for floor in self {
+            let floor = floor?;
+            let previous = history.final_floor;
+            history.moves += 1;
+            history.final_floor = floor;
+            let pos = history.moves;
+
+            if floor > history.highest.0 {
+                history.highest = (floor, pos);
+            }
+            if floor < history.lowest.0 {
+                history.lowest = (floor, pos);
+            }
+
+            match (previous, floor) {
+                (0, -1) => history.basement_events.push(BasementEvent::Entered(pos)),
+                (-1, 0) => history.basement_events.push(BasementEvent::Left(pos)),
+                _ => {}
+            }
+
+            *history.time_on_floor.entry(floor).or_insert(0) += 1;
+            if floor == 0 {
+                history.ground_revisits += 1;
+            }
+        }
+
+        Ok(history)
+    }
+}
+
+/// The floor after every move, indexed for answering queries about the walk.
+/// Positions count moves from 1, with 0 being the start.
+struct FloorIndex {
+    /// The floor at every position.
+    floors: Vec<i64>,
+    /// Every position on each floor, in ascending order.
+    positions: HashMap<i64, Vec<usize>>,
+}
+
+impl FloorIndex {
+    fn build<R: Read>(santa: Santa<R>) -> io::Result<Self> {
+        let mut floors = vec![0];
+        let mut positions = HashMap::from([(0, vec![0])]);
+
+        for floor in santa {
+            let floor = floor?;
+            positions
+                .entry(floor)
+                .or_insert_with(Vec::new)
+                .push(floors.len());
+            floors.push(floor);
+        }
+
+        Ok(Self { floors, positions })
+    }
+
+    /// The first position on floor `n`. As Santa moves one floor at a time,
+    /// this is also the first time he reaches a floor at least as far from the ground.
+    fn first_reaching(&self, n: i64) -> Option<usize> {
+        self.positions_at(n).first().copied()
+    }
+
+    fn positions_at(&self, n: i64) -> &[usize] {
+        self.positions.get(&n).map_or(&[], Vec::as_slice)
+    }
+
+    fn floor_after(&self, k: usize) -> Option<i64> {
+        self.floors.get(k).copied()
+    }
+
+    /// The position of the move which first takes Santa into the basement (part 2).
+    fn basement_pos(&self) -> Option<usize> {
+        self.first_reaching(-1)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum BasementEvent {
+    /// The position of the move which took Santa from the ground floor to the basement.
+    Entered(usize),
+    /// The position of the move which took Santa back up to the ground floor.
+    Left(usize),
+}
+
+impl fmt::Display for BasementEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BasementEvent::Entered(pos) => write!(f, "entered the basement at {pos}"),
+            BasementEvent::Left(pos) => write!(f, "left the basement at {pos}"),
+        }
+    }
+}
+
+/// Where Santa went on his walk. Positions count moves from 1, with 0 being the start.
+#[derive(Debug)]
+struct History {
+    moves: usize,
+    final_floor: i64,
+    /// The highest floor reached, and the first position on it.
+    highest: (i64, usize),
+    /// The lowest floor reached, and the first position on it.
+    lowest: (i64, usize),
+    basement_events: Vec<BasementEvent>,
+    /// How many positions, including the start, Santa spent on each floor.
+    time_on_floor: BTreeMap<i64, usize>,
+    /// How many times Santa came back to the ground floor.
+    ground_revisits: usize,
+}
+
+impl fmt::Display for History {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Santa made {} moves and ended up on floor {}.",
+            self.moves, self.final_floor
+        )?;
+        writeln!(
+            f,
+            "The highest floor was {}, first reached at {}.",
+            self.highest.0, self.highest.1
+        )?;
+        writeln!(
+            f,
+            "The lowest floor was {}, first reached at {}.",
+            self.lowest.0, self.lowest.1
+        )?;
+        writeln!(
+            f,
+            "He came back to the ground floor {} time(s).",
+            self.ground_revisits
+        )?;
+
+        writeln!(f, "Basement visits:")?;
+        for event in &self.basement_events {
+            writeln!(f, "  {event}")?;
+        }
+
+        writeln!(f, "Time on each floor:")?;
+        for (floor, time) in &self.time_on_floor {
+            writeln!(f, "  {floor:>4}: {time}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for Santa<R> {
+    type Item = io::Result<i64>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            while self.pos < self.len {
+                let byte = self.chunk[self.pos];
+                self.pos += 1;
+
+                match Move::try_from(byte) {
+                    Ok(Move::UpFloor) => self.current_floor += 1,
+                    Ok(Move::DownFloor) => self.current_floor -= 1,
+                    Err(_) => continue,
+                }
+
+                return Some(Ok(self.current_floor));
+            }
+
+            match self.moves.read(&mut self.chunk) {
+                Ok(0) => return None,
+                Ok(len) => {
+                    self.pos = 0;
+                    self.len = len;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Some(Err(e)),
             }
         }
+    }
+}
 
-        (self.current_floor, basement_instruction_pos)
+/// Answers queries like `first N`, `at N`, `after K` and `basement`, given as pairs of arguments.
+fn query(
+    moves: impl Read,
+    mut args: impl Iterator<Item = String>,
+    out: &mut impl Write,
+) -> Result<()> {
+    const ERR: &str = "invalid query!";
+
+    let index = FloorIndex::build(Santa::new(moves))?;
+
+    while let Some(kind) = args.next() {
+        if kind == "basement" {
+            match index.basement_pos() {
+                Some(pos) => writeln!(out, "basement: {pos}")?,
+                None => writeln!(out, "basement: never")?,
+            }
+            continue;
+        }
+
+        let arg = args.next().ok_or(ERR)?;
+        match kind.as_str() {
+            "first" => match index.first_reaching(arg.parse()?) {
+                Some(pos) => writeln!(out, "first {arg}: {pos}")?,
+                None => writeln!(out, "first {arg}: never")?,
+            },
+            "at" => writeln!(out, "at {arg}: {:?}", index.positions_at(arg.parse()?))?,
+            "after" => match index.floor_after(arg.parse()?) {
+                Some(floor) => writeln!(out, "after {arg}: {floor}")?,
+                None => writeln!(out, "after {arg}: out of moves")?,
+            },
+            _ => return Err(ERR.into()),
+        }
     }
+
+    Ok(())
 }
 
 fn main() -> Result<()> {
-    let moves = fs::read_to_string("input.txt")?;
+    let moves = File::open("input.txt")?;
 
-    let (resulting_floor, basement_pos) = Santa::new(&moves).follow_instructions();
     let mut stdout = io::stdout().lock();
 
+    // `day01 --report` describes the whole walk,
+    // `day01 --query first 5 at -1 after 100 basement` answers questions about it.
+    match env::args().nth(1).as_deref() {
+        Some("--report") => {
+            write!(stdout, "{}", Santa::new(moves).history()?)?;
+            return Ok(());
+        }
+        Some("--query") => return query(moves, env::args().skip(2), &mut stdout),
+        Some(arg) => return Err(format!("unknown argument: {arg}").into()),
+        None => {}
+    }
+
+    let (resulting_floor, basement_pos) = Santa::new(moves).follow_instructions()?;
+
     writeln!(stdout, "Santa is on floor {resulting_floor}!")?;
 
     if let Some(pos) = basement_pos {
@@ -125,13 +372,13 @@ mod tests {
 
     // Tests are from the puzzle description at the top.
 
-    fn res_floor(input: &str) -> i32 {
-        let (resulting_floor, _) = Santa::new(input).follow_instructions();
+    fn res_floor(input: &str) -> i64 {
+        let (resulting_floor, _) = Santa::new(input.as_bytes()).follow_instructions().unwrap();
         resulting_floor
     }
 
     fn basement_pos(input: &str) -> usize {
-        let (_, basement_pos) = Santa::new(input).follow_instructions();
+        let (_, basement_pos) = Santa::new(input.as_bytes()).follow_instructions().unwrap();
         basement_pos.unwrap()
     }
 
@@ -153,4 +400,146 @@ mod tests {
         assert_eq!(basement_pos(")"), 1);
         assert_eq!(basement_pos("()())"), 5);
     }
+
+    /// Hands out its bytes one at a time, then fails if told to.
+    struct Trickle<'a> {
+        bytes: &'a [u8],
+        fail: bool,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.bytes.split_first() {
+                Some((&b, rest)) => {
+                    buf[0] = b;
+                    self.bytes = rest;
+                    Ok(1)
+                }
+                None if self.fail => Err(io::Error::other("disconnected")),
+                None => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn floors() {
+        let floors: Vec<i64> = Santa::new("(()\n)x)".as_bytes())
+            .map(|floor| floor.unwrap())
+            .collect();
+        assert_eq!(floors, [1, 2, 1, 0, -1]);
+
+        let trickle = Trickle {
+            bytes: b"()())",
+            fail: false,
+        };
+        let floors: Vec<i64> = Santa::new(trickle).map(|floor| floor.unwrap()).collect();
+        assert_eq!(floors, [1, 0, 1, 0, -1]);
+
+        let trickle = Trickle {
+            bytes: b"((",
+            fail: true,
+        };
+        assert!(Santa::new(trickle).follow_instructions().is_err());
+
+        // The error still counts once the basement has been found.
+        let trickle = Trickle {
+            bytes: b")(",
+            fail: true,
+        };
+        assert!(Santa::new(trickle).follow_instructions().is_err());
+    }
+
+    #[test]
+    fn across_chunks() {
+        let moves = "(".repeat(CHUNK_SIZE + 5) + &")".repeat(2 * CHUNK_SIZE);
+        let (floor, basement_pos) = Santa::new(moves.as_bytes()).follow_instructions().unwrap();
+
+        assert_eq!(floor, 5 - CHUNK_SIZE as i64);
+        assert_eq!(basement_pos, Some(2 * CHUNK_SIZE + 11));
+    }
+
+    #[test]
+    fn history() {
+        let history = Santa::new("(()))())((".as_bytes()).history().unwrap();
+
+        assert_eq!(history.moves, 10);
+        assert_eq!(history.final_floor, 0);
+        assert_eq!(history.highest, (2, 2));
+        assert_eq!(history.lowest, (-2, 8));
+        assert_eq!(
+            history.basement_events,
+            [
+                BasementEvent::Entered(5),
+                BasementEvent::Left(6),
+                BasementEvent::Entered(7),
+                BasementEvent::Left(10)
+            ]
+        );
+        assert_eq!(
+            history.time_on_floor,
+            BTreeMap::from([(-2, 1), (-1, 3), (0, 4), (1, 2), (2, 1)])
+        );
+        assert_eq!(history.ground_revisits, 3);
+
+        let report = history.to_string();
+        assert!(report.starts_with("Santa made 10 moves and ended up on floor 0.\n"));
+        assert!(report.contains("\n  left the basement at 6\n"));
+        assert!(report.contains("\n    -2: 1\n"));
+    }
+
+    #[test]
+    fn empty_history() {
+        let history = Santa::new("".as_bytes()).history().unwrap();
+
+        assert_eq!((history.highest, history.lowest), ((0, 0), (0, 0)));
+        assert_eq!(history.time_on_floor, BTreeMap::from([(0, 1)]));
+        assert_eq!(history.ground_revisits, 0);
+    }
+
+    #[test]
+    fn floor_index() {
+        let index = FloorIndex::build(Santa::new("(()))())((".as_bytes())).unwrap();
+
+        assert_eq!(index.first_reaching(0), Some(0));
+        assert_eq!(index.first_reaching(2), Some(2));
+        assert_eq!(index.first_reaching(-2), Some(8));
+        assert_eq!(index.first_reaching(3), None);
+        assert_eq!(index.positions_at(-1), [5, 7, 9]);
+        assert_eq!(index.positions_at(0), [0, 4, 6, 10]);
+        assert!(index.positions_at(5).is_empty());
+        assert_eq!(index.floor_after(0), Some(0));
+        assert_eq!(index.floor_after(8), Some(-2));
+        assert_eq!(index.floor_after(11), None);
+        assert_eq!(index.basement_pos(), Some(5));
+
+        for input in [")", "()())", "((("] {
+            let index = FloorIndex::build(Santa::new(input.as_bytes())).unwrap();
+            let (floor, basement_pos) = Santa::new(input.as_bytes()).follow_instructions().unwrap();
+
+            assert_eq!(index.basement_pos(), basement_pos);
+            assert_eq!(index.floor_after(input.len()), Some(floor));
+        }
+    }
+
+    #[test]
+    fn queries() {
+        let args = [
+            "first", "-1", "at", "1", "after", "3", "after", "9", "basement",
+        ];
+        let mut out = vec![];
+        query(
+            "()())".as_bytes(),
+            args.iter().map(|a| a.to_string()),
+            &mut out,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "first -1: 5\nat 1: [1, 3]\nafter 3: 1\nafter 9: out of moves\nbasement: 5\n"
+        );
+
+        let args = ["first"].iter().map(|a| a.to_string());
+        assert!(query("(".as_bytes(), args, &mut vec![]).is_err());
+    }
 }
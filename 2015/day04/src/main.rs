@@ -19,39 +19,546 @@ For example:
 Now find one that starts with six zeroes.
 */
 
+mod sha256;
+
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::env;
 use std::error::Error;
+use std::fmt;
 use std::fs;
 use std::io::{self, Write};
+use std::marker::PhantomData;
+use std::mem;
+use std::path::PathBuf;
 use std::result::Result as StdResult;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 type Result<T> = StdResult<T, Box<dyn Error>>;
 
-fn find_lowest_n(input: &str, n_zeroes: usize) -> usize {
-    let mut n = 1;
-    let zeroes = "0".repeat(n_zeroes);
+/// A hash function the miner can search with.
+trait Hash {
+    const NAME: &'static str;
+
+    type Digest: AsRef<[u8]>;
+
+    fn digest(data: &[u8]) -> Self::Digest;
+}
+
+struct Md5;
+
+impl Hash for Md5 {
+    const NAME: &'static str = "MD5";
+
+    type Digest = [u8; 16];
+
+    fn digest(data: &[u8]) -> Self::Digest {
+        md5::compute(data).0
+    }
+}
+
+struct Sha256;
+
+impl Hash for Sha256 {
+    const NAME: &'static str = "SHA-256";
+
+    type Digest = [u8; 32];
+
+    fn digest(data: &[u8]) -> Self::Digest {
+        sha256::compute(data)
+    }
+}
+
+/// The secret key followed by a decimal suffix, kept in a single buffer
+/// so that the suffix can be incremented in place.
+struct Suffixed {
+    buf: Vec<u8>,
+    key_len: usize,
+}
+
+impl Suffixed {
+    fn new(key: &str, n: usize) -> Self {
+        let mut buf = Vec::with_capacity(key.len() + 20);
+        buf.extend_from_slice(key.as_bytes());
+        buf.extend_from_slice(n.to_string().as_bytes());
+
+        Self {
+            buf,
+            key_len: key.len(),
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Add one to the suffix, carrying like on paper.
+    fn increment(&mut self) {
+        for digit in self.buf[self.key_len..].iter_mut().rev() {
+            if *digit == b'9' {
+                *digit = b'0';
+            } else {
+                *digit += 1;
+                return;
+            }
+        }
+
+        // All digits were nines, so the suffix gains a digit: 999 -> 1000.
+        self.buf.insert(self.key_len, b'1');
+    }
+}
+
+const ERR: &str = "invalid target!";
+
+/// What a digest has to look like for its `n` to count as a solution.
+#[derive(Debug, PartialEq)]
+enum Target {
+    /// The digest, written in hex, starts with these nibbles.
+    HexPrefix(Vec<u8>),
+    /// The digest starts with at least this many zero bits.
+    ZeroBits(usize),
+    /// The digest, read as a big-endian number, is less than this one.
+    /// The threshold is padded with zeroes on the right to the digest's width.
+    Below(Vec<u8>),
+}
+
+impl Target {
+    /// The puzzle's target: `n_zeroes` leading zeroes in hex.
+    fn zeroes(n_zeroes: usize) -> Self {
+        Target::ZeroBits(n_zeroes * 4)
+    }
+
+    /// How many hashes it takes on average to find a digest meeting the target.
+    fn expected_tries(&self) -> f64 {
+        match self {
+            Target::HexPrefix(nibbles) => 16f64.powi(nibbles.len() as i32),
+            Target::ZeroBits(n_bits) => 2f64.powi(*n_bits as i32),
+            Target::Below(threshold) => {
+                let fraction: f64 = threshold
+                    .iter()
+                    .rev()
+                    .fold(0.0, |acc, &b| (acc + f64::from(b)) / 256.0);
+                1.0 / fraction
+            }
+        }
+    }
+
+    /// Can a digest of `digest_len` bytes meet the target at all?
+    fn fits(&self, digest_len: usize) -> bool {
+        match self {
+            Target::HexPrefix(nibbles) => nibbles.len() <= digest_len * 2,
+            Target::ZeroBits(n_bits) => *n_bits <= digest_len * 8,
+            Target::Below(_) => true,
+        }
+    }
+
+    fn is_met(&self, digest: &[u8]) -> bool {
+        match self {
+            Target::HexPrefix(nibbles) => nibbles.iter().enumerate().all(|(i, &nibble)| {
+                digest.get(i / 2).is_some_and(|&b| {
+                    let digest_nibble = if i % 2 == 0 { b >> 4 } else { b & 0xf };
+                    digest_nibble == nibble
+                })
+            }),
+            Target::ZeroBits(n_bits) => has_leading_zero_bits(digest, *n_bits),
+            Target::Below(threshold) => {
+                let len = threshold.len().min(digest.len());
+                match digest[..len].cmp(&threshold[..len]) {
+                    Ordering::Less => true,
+                    Ordering::Greater => false,
+                    // The rest of the threshold is padding, so the digest is
+                    // below it only if the threshold has nonzero bytes left.
+                    Ordering::Equal => threshold[len..].iter().any(|&b| b != 0),
+                }
+            }
+        }
+    }
+}
+
+fn parse_nibbles(s: &str) -> StdResult<Vec<u8>, &'static str> {
+    s.chars()
+        .map(|c| c.to_digit(16).map(|d| d as u8).ok_or(ERR))
+        .collect()
+}
+
+impl FromStr for Target {
+    type Err = &'static str;
+
+    /// Accepts `zeroes:N`, `bits:N`, `prefix:HEX` and `below:HEX`.
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        let (kind, arg) = s.split_once(':').ok_or(ERR)?;
+
+        match kind {
+            "zeroes" => {
+                let n_zeroes: usize = arg.parse().map_err(|_| ERR)?;
+                Ok(Target::ZeroBits(n_zeroes.checked_mul(4).ok_or(ERR)?))
+            }
+            "bits" => Ok(Target::ZeroBits(arg.parse().map_err(|_| ERR)?)),
+            "prefix" => Ok(Target::HexPrefix(parse_nibbles(arg)?)),
+            "below" => {
+                let nibbles = parse_nibbles(arg)?;
+                let threshold: Vec<u8> = nibbles
+                    .chunks(2)
+                    .map(|pair| pair[0] << 4 | pair.get(1).unwrap_or(&0))
+                    .collect();
+
+                // No digest is below zero.
+                if threshold.iter().all(|&b| b == 0) {
+                    return Err(ERR);
+                }
+
+                Ok(Target::Below(threshold))
+            }
+            _ => Err(ERR),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::HexPrefix(nibbles) => {
+                write!(f, "prefix:")?;
+                nibbles.iter().try_for_each(|n| write!(f, "{n:x}"))
+            }
+            Target::ZeroBits(n_bits) => write!(f, "bits:{n_bits}"),
+            Target::Below(threshold) => write!(f, "below:{}", hex(threshold)),
+        }
+    }
+}
+
+/// Does the digest start with at least `n_bits` zero bits?
+fn has_leading_zero_bits(digest: &[u8], n_bits: usize) -> bool {
+    let full_bytes = n_bits / 8;
+    let rest = n_bits % 8;
+
+    if full_bytes > digest.len() {
+        return false;
+    }
+
+    digest[..full_bytes].iter().all(|&b| b == 0)
+        && (rest == 0
+            || digest
+                .get(full_bytes)
+                .is_some_and(|&b| b.leading_zeros() as usize >= rest))
+}
+
+/// A number that, appended to the secret key, meets the target.
+struct Solution {
+    n: usize,
+    digest: Vec<u8>,
+}
+
+/// How many hashes the miner computes between calls to its progress callback.
+const PROGRESS_INTERVAL: usize = 1 << 16;
+
+/// How often a long search reports its progress.
+const PROGRESS_PERIOD: Duration = Duration::from_secs(1);
 
-    loop {
-        let md5_input = format!("{input}{n}");
-        let digest = md5::compute(md5_input);
+/// Yields every solution for a key and target, lowest `n` first.
+struct Miner<'a, H> {
+    hash_input: Suffixed,
+    target: &'a Target,
+    n: usize,
+    on_progress: Option<Box<dyn FnMut(usize) + 'a>>,
+    hash: PhantomData<H>,
+}
 
-        if format!("{digest:x}").starts_with(&zeroes) {
-            break n;
+impl<'a, H: Hash> Miner<'a, H> {
+    fn new(input: &str, target: &'a Target) -> Self {
+        Self::starting_at(input, target, 1)
+    }
+
+    /// Start the search at `n` instead of 1, e.g. to resume it.
+    fn starting_at(input: &str, target: &'a Target, n: usize) -> Self {
+        Self {
+            hash_input: Suffixed::new(input, n),
+            target,
+            n,
+            on_progress: None,
+            hash: PhantomData,
         }
+    }
+
+    /// Call `f` with the next `n` to be tried every `PROGRESS_INTERVAL` hashes.
+    fn on_progress(mut self, f: impl FnMut(usize) + 'a) -> Self {
+        self.on_progress = Some(Box::new(f));
+        self
+    }
+}
+
+impl<H: Hash> Iterator for Miner<'_, H> {
+    type Item = Solution;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let digest = H::digest(self.hash_input.as_bytes());
+            let n = self.n;
+
+            self.hash_input.increment();
+            self.n += 1;
+
+            if self.target.is_met(digest.as_ref()) {
+                break Some(Solution {
+                    n,
+                    digest: digest.as_ref().to_vec(),
+                });
+            }
 
-        n += 1;
+            // Only now is everything before `self.n` known not to be a solution,
+            // so that a checkpoint saved by the callback doesn't skip one.
+            if self.n.is_multiple_of(PROGRESS_INTERVAL) {
+                if let Some(f) = &mut self.on_progress {
+                    f(self.n);
+                }
+            }
+        }
     }
 }
 
+fn find_lowest_n<H: Hash>(input: &str, n_zeroes: usize) -> usize {
+    let target = Target::zeroes(n_zeroes);
+    let mut miner = Miner::<H>::new(input, &target);
+    miner.next().unwrap().n
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Where an interrupted search for some key, hash function and target left off.
+#[derive(Debug, PartialEq)]
+struct Checkpoint {
+    hash: String,
+    target: String,
+    key: String,
+    /// The next `n` to be tried.
+    n: usize,
+    /// How many solutions were found before `n`.
+    found: usize,
+}
+
+/// A file holding one checkpoint per line, as `HASH<tab>TARGET<tab>N<tab>FOUND<tab>KEY`.
+struct Checkpoints {
+    path: PathBuf,
+    entries: Vec<Checkpoint>,
+}
+
+impl Checkpoints {
+    /// Read the checkpoints in `path`. A missing file has none.
+    fn load(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let mut entries = vec![];
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        for line in contents.lines() {
+            let mut spl = line.splitn(5, '\t');
+            let hash = spl.next().ok_or(CHECKPOINT_ERR)?.to_string();
+            let target = spl.next().ok_or(CHECKPOINT_ERR)?.to_string();
+            let n = spl.next().ok_or(CHECKPOINT_ERR)?.parse()?;
+            let found = spl.next().ok_or(CHECKPOINT_ERR)?.parse()?;
+            let key = spl.next().ok_or(CHECKPOINT_ERR)?.to_string();
+
+            entries.push(Checkpoint {
+                hash,
+                target,
+                key,
+                n,
+                found,
+            });
+        }
+
+        Ok(Self { path, entries })
+    }
+
+    fn get<H: Hash>(&self, key: &str, target: &Target) -> Option<&Checkpoint> {
+        let target = target.to_string();
+        self.entries
+            .iter()
+            .find(|c| c.hash == H::NAME && c.key == key && c.target == target)
+    }
+
+    fn set<H: Hash>(&mut self, key: &str, target: &Target, n: usize, found: usize) {
+        self.remove::<H>(key, target);
+        self.entries.push(Checkpoint {
+            hash: H::NAME.to_string(),
+            target: target.to_string(),
+            key: key.to_string(),
+            n,
+            found,
+        });
+    }
+
+    fn remove<H: Hash>(&mut self, key: &str, target: &Target) {
+        let target = target.to_string();
+        self.entries
+            .retain(|c| !(c.hash == H::NAME && c.key == key && c.target == target));
+    }
+
+    /// Write the checkpoints out, replacing the file atomically.
+    fn save(&self) -> Result<()> {
+        let mut contents = String::new();
+
+        for c in &self.entries {
+            if !is_checkpointable(&c.key) {
+                return Err(CHECKPOINT_ERR.into());
+            }
+            contents += &format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                c.hash, c.target, c.n, c.found, c.key
+            );
+        }
+
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, contents)?;
+        fs::rename(tmp_path, &self.path)?;
+
+        Ok(())
+    }
+}
+
+const CHECKPOINT_ERR: &str = "invalid checkpoint!";
+
+/// Can the key be written on a line of the checkpoint file and read back unchanged?
+/// Reading drops a trailing `\r` along with the newline, so neither may appear.
+fn is_checkpointable(key: &str) -> bool {
+    !key.contains(['\n', '\r'])
+}
+
+/// Describe how far a search got, given the hash rate so far.
+///
+/// Since every hash is an independent try, the expected time to the next
+/// solution doesn't depend on how long the search has been running.
+fn progress_line(n: usize, hashes_per_sec: f64, expected_tries: f64) -> String {
+    let eta = expected_tries / hashes_per_sec;
+    format!(
+        "n = {n}, {:.2} MH/s, next solution expected in ~{eta:.0}s",
+        hashes_per_sec / 1e6
+    )
+}
+
+/// List the first `count` solutions for `target`, reporting progress on stderr
+/// and, if `checkpoint_path` is given, resuming from and saving checkpoints there.
+fn mine<H: Hash>(
+    input: &str,
+    target: &Target,
+    count: usize,
+    checkpoint_path: Option<&str>,
+    stdout: &mut impl Write,
+) -> Result<()> {
+    if !target.fits(mem::size_of::<H::Digest>()) {
+        return Err(format!("no {} digest can meet {target}!", H::NAME).into());
+    }
+
+    // Find out now rather than when the first checkpoint is saved, possibly minutes in.
+    if checkpoint_path.is_some() && !is_checkpointable(input) {
+        return Err("a key with line breaks can't be checkpointed!".into());
+    }
+
+    let checkpoints = checkpoint_path.map(Checkpoints::load).transpose()?;
+    let (start_n, found) = checkpoints
+        .as_ref()
+        .and_then(|c| c.get::<H>(input, target))
+        .map_or((1, 0), |c| (c.n, c.found));
+
+    if found > 0 || start_n > 1 {
+        eprintln!("Resuming at n = {start_n} with {found} solution(s) already found");
+    }
+
+    let checkpoints = RefCell::new(checkpoints);
+    let found = Cell::new(found);
+    let expected_tries = target.expected_tries();
+    let start = Instant::now();
+    let mut last_report = start;
+
+    let miner = Miner::<H>::starting_at(input, target, start_n).on_progress(|n| {
+        let now = Instant::now();
+        if now.duration_since(last_report) < PROGRESS_PERIOD {
+            return;
+        }
+        last_report = now;
+
+        let hashes_per_sec = (n - start_n) as f64 / now.duration_since(start).as_secs_f64();
+        eprintln!("{}", progress_line(n, hashes_per_sec, expected_tries));
+
+        if let Some(c) = checkpoints.borrow_mut().as_mut() {
+            c.set::<H>(input, target, n, found.get());
+            if let Err(e) = c.save() {
+                eprintln!("Could not save checkpoint: {e}");
+            }
+        }
+    });
+
+    for Solution { n, digest } in miner.take(count.saturating_sub(found.get())) {
+        writeln!(stdout, "{n}: {}", hex(&digest))?;
+        found.set(found.get() + 1);
+
+        if let Some(c) = checkpoints.borrow_mut().as_mut() {
+            c.set::<H>(input, target, n + 1, found.get());
+            c.save()?;
+        }
+    }
+
+    // The search is complete, so there is nothing left to resume.
+    if let Some(c) = checkpoints.borrow_mut().as_mut() {
+        c.remove::<H>(input, target);
+        c.save()?;
+    }
+
+    Ok(())
+}
+
 fn main() -> Result<()> {
     let input = fs::read_to_string("input.txt")?;
+    let mut args: Vec<String> = env::args().skip(1).collect();
 
-    let n5 = find_lowest_n(&input, 5);
-    let n6 = find_lowest_n(&input, 6);
+    // `--hash md5|sha256` picks the hash function to mine with.
+    let hash = match args.iter().position(|a| a == "--hash") {
+        Some(i) if i + 1 < args.len() => args.drain(i..i + 2).nth(1).unwrap(),
+        Some(_) => return Err("missing hash function!".into()),
+        None => "md5".to_string(),
+    };
+
+    match hash.as_str() {
+        "md5" => run::<Md5>(&input, args),
+        "sha256" => run::<Sha256>(&input, args),
+        _ => Err("unknown hash function!".into()),
+    }
+}
+
+fn run<H: Hash>(input: &str, args: Vec<String>) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    let mut args = args.into_iter();
+
+    // `day04 TARGET [COUNT] [CHECKPOINT_FILE]` lists the first COUNT solutions for TARGET.
+    if let Some(target) = args.next() {
+        let target: Target = target.parse()?;
+        let count = args.next().map(|c| c.parse()).transpose()?.unwrap_or(1);
+        let checkpoint_path = args.next();
+
+        return mine::<H>(
+            input,
+            &target,
+            count,
+            checkpoint_path.as_deref(),
+            &mut stdout,
+        );
+    }
+
+    let n5 = find_lowest_n::<H>(input, 5);
+    let n6 = find_lowest_n::<H>(input, 6);
 
     writeln!(
-        io::stdout(),
-        "Lowest possible n for the MD5 hash to start with 5 zeroes: {n5}\n\
-         Lowest possible n for the MD5 hash to start with 6 zeroes: {n6}"
+        stdout,
+        "Lowest possible n for the {name} hash to start with 5 zeroes: {n5}\n\
+         Lowest possible n for the {name} hash to start with 6 zeroes: {n6}",
+        name = H::NAME
     )?;
 
     Ok(())
@@ -65,10 +572,235 @@ mod tests {
 
     #[test]
     fn part1() {
-        let n = find_lowest_n("abcdef", 5);
+        let n = find_lowest_n::<Md5>("abcdef", 5);
         assert_eq!(n, 609043);
 
-        let n = find_lowest_n("pqrstuv", 5);
+        let n = find_lowest_n::<Md5>("pqrstuv", 5);
         assert_eq!(n, 1048970);
     }
+
+    #[test]
+    fn md5_vectors() {
+        // From RFC 1321.
+        assert_eq!(hex(&Md5::digest(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(
+            hex(&Md5::digest(b"abc")),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+        assert_eq!(
+            hex(&Md5::digest(b"message digest")),
+            "f96b697d7cb7938d525a2f31aaf161d0"
+        );
+    }
+
+    #[test]
+    fn sha256_vectors() {
+        // From FIPS 180-4 and the NIST example values.
+        assert_eq!(
+            hex(&Sha256::digest(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(&Sha256::digest(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex(&Sha256::digest(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            hex(&Sha256::digest(&[b'a'; 1000])),
+            "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+        );
+    }
+
+    #[test]
+    fn sha256_mining() {
+        assert_eq!(find_lowest_n::<Sha256>("abcdef", 4), 71479);
+        assert_eq!(find_lowest_n::<Sha256>("pqrstuv", 4), 12944);
+    }
+
+    #[test]
+    fn suffix_increment() {
+        let mut suffixed = Suffixed::new("key", 1);
+
+        for n in 1..=1001 {
+            assert_eq!(suffixed.as_bytes(), format!("key{n}").as_bytes());
+            suffixed.increment();
+        }
+    }
+
+    #[test]
+    fn leading_zero_bits() {
+        let digest = [0x00, 0x00, 0x0f, 0xff];
+
+        assert!(has_leading_zero_bits(&digest, 0));
+        assert!(has_leading_zero_bits(&digest, 16));
+        assert!(has_leading_zero_bits(&digest, 20));
+        assert!(!has_leading_zero_bits(&digest, 21));
+        assert!(!has_leading_zero_bits(&digest, 40));
+    }
+
+    #[test]
+    fn targets() {
+        let digest = [0x00, 0x00, 0x0f, 0xff];
+
+        assert!("zeroes:5".parse::<Target>().unwrap().is_met(&digest));
+        assert!(!"zeroes:6".parse::<Target>().unwrap().is_met(&digest));
+        assert!("prefix:000".parse::<Target>().unwrap().is_met(&digest));
+        assert!("prefix:00000ff".parse::<Target>().unwrap().is_met(&digest));
+        assert!(!"prefix:00001".parse::<Target>().unwrap().is_met(&digest));
+        assert!("below:00001".parse::<Target>().unwrap().is_met(&digest));
+        assert!("below:00000fff01"
+            .parse::<Target>()
+            .unwrap()
+            .is_met(&digest));
+        assert!(!"below:00000fff".parse::<Target>().unwrap().is_met(&digest));
+        assert!(!"below:00000e".parse::<Target>().unwrap().is_met(&digest));
+
+        assert!("bits".parse::<Target>().is_err());
+        assert!("prefix:xyz".parse::<Target>().is_err());
+        assert!("sha:1".parse::<Target>().is_err());
+        assert!("below:".parse::<Target>().is_err());
+        assert!("below:00".parse::<Target>().is_err());
+        assert!("zeroes:4611686018427387904".parse::<Target>().is_err());
+    }
+
+    #[test]
+    fn unreachable_targets() {
+        assert!(Target::ZeroBits(128).fits(16));
+        assert!(!Target::ZeroBits(129).fits(16));
+        assert!(Target::ZeroBits(129).fits(32));
+        assert!(!"prefix:000000000000000000000000000000000"
+            .parse::<Target>()
+            .unwrap()
+            .fits(16));
+
+        let target = Target::ZeroBits(129);
+        let result = mine::<Md5>("abcdef", &target, 1, None, &mut vec![]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "no MD5 digest can meet bits:129!"
+        );
+    }
+
+    #[test]
+    fn target_display_round_trips() {
+        for s in ["bits:20", "prefix:00abc", "below:0000ff"] {
+            assert_eq!(s.parse::<Target>().unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn expected_tries() {
+        assert_eq!(Target::zeroes(5).expected_tries(), 16f64.powi(5));
+        assert_eq!(Target::ZeroBits(3).expected_tries(), 8.0);
+        assert_eq!(
+            "prefix:abc".parse::<Target>().unwrap().expected_tries(),
+            4096.0
+        );
+        assert_eq!(
+            "below:01".parse::<Target>().unwrap().expected_tries(),
+            256.0
+        );
+        assert_eq!(
+            "below:0080".parse::<Target>().unwrap().expected_tries(),
+            512.0
+        );
+    }
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let path = env::temp_dir().join(format!("day04-{}.checkpoint", std::process::id()));
+        let target = Target::zeroes(5);
+
+        let mut checkpoints = Checkpoints::load(&path).unwrap();
+        assert!(checkpoints.entries.is_empty());
+        checkpoints.set::<Md5>("abcdef", &target, 600000, 0);
+        checkpoints.save().unwrap();
+
+        let mut out = vec![];
+        mine::<Md5>("abcdef", &target, 1, path.to_str(), &mut out).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .starts_with("609043: 000001dbbfa"));
+
+        // The finished search leaves no checkpoint behind.
+        let checkpoints = Checkpoints::load(&path).unwrap();
+        assert_eq!(checkpoints.get::<Md5>("abcdef", &target), None);
+
+        for key in ["abc\ndef", "abcdef\r"] {
+            let result = mine::<Md5>(key, &target, 1, path.to_str(), &mut vec![]);
+            assert!(result.is_err());
+        }
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn first_k_solutions() {
+        let target = Target::zeroes(5);
+        let solutions: Vec<Solution> = Miner::<Md5>::new("abcdef", &target).take(2).collect();
+
+        assert_eq!(solutions[0].n, 609043);
+        assert!(hex(&solutions[0].digest).starts_with("000001dbbfa"));
+        assert!(solutions[1].n > 609043);
+        assert!(hex(&solutions[1].digest).starts_with("00000"));
+    }
+
+    #[test]
+    fn no_progress_past_unreported_solution() {
+        // A solution just before a progress call.
+        let n = PROGRESS_INTERVAL - 1;
+        let digest = Md5::digest(format!("abcdef{n}").as_bytes());
+        let target: Target = format!("prefix:{}", &hex(&digest)[..8]).parse().unwrap();
+
+        let progress = RefCell::new(vec![]);
+        let mut miner = Miner::<Md5>::starting_at("abcdef", &target, n - 5)
+            .on_progress(|n| progress.borrow_mut().push(n));
+
+        assert_eq!(miner.next().unwrap().n, n);
+        drop(miner);
+        assert!(progress.borrow().is_empty());
+    }
+
+    /// `find_lowest_n` before it reused its buffer, formatting the input
+    /// and the hex digest into new strings on every try.
+    fn find_lowest_n_alloc(input: &str, n_zeroes: usize) -> usize {
+        let mut n = 1;
+        let zeroes = "0".repeat(n_zeroes);
+
+        loop {
+            let md5_input = format!("{input}{n}");
+            let digest = md5::compute(md5_input);
+
+            if format!("{digest:x}").starts_with(&zeroes) {
+                break n;
+            }
+
+            n += 1;
+        }
+    }
+
+    /// Times the allocating search against the in-place one. The numbers only
+    /// mean something from a release build: `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench() {
+        for key in ["abcdef", "pqrstuv"] {
+            let start = Instant::now();
+            let expected = find_lowest_n_alloc(key, 5);
+            let alloc_time = start.elapsed();
+
+            let start = Instant::now();
+            let n = find_lowest_n::<Md5>(key, 5);
+            let time = start.elapsed();
+
+            println!("{key}: {alloc_time:?} (allocating) -> {time:?} (in-place)");
+
+            assert_eq!(n, expected);
+        }
+    }
 }
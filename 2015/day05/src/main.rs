@@ -45,61 +45,420 @@ For example:
 How many strings are nice under these new rules?
 */
 
+use std::collections::HashMap;
+use std::env;
 use std::error::Error;
+use std::fmt;
 use std::fs;
 use std::io::{self, Write};
 use std::result::Result as StdResult;
+use std::str::FromStr;
 
 type Result<T> = StdResult<T, Box<dyn Error>>;
 
-fn is_nice1(s: &str) -> bool {
-    let chr_vec: Vec<char> = s.chars().collect();
+const ERR: &str = "invalid rule!";
+
+/// One property a string must have to be nice.
+#[derive(Debug, PartialEq)]
+enum Rule {
+    /// At least `min` of the characters are from `class`.
+    MinCount { class: String, min: usize },
+    /// Some letter appears twice with exactly `gap` letters between,
+    /// like `xx` for a gap of 0 or `xyx` for a gap of 1.
+    RepeatedLetter { gap: usize },
+    /// Some run of `len` letters appears at least twice without overlapping.
+    RepeatedNgram { len: usize },
+    /// None of the substrings appear.
+    Forbidden(Vec<String>),
+}
+
+/// What a rule found in a string. Positions are character indices, starting at 0.
+#[derive(Debug, PartialEq)]
+enum Evidence {
+    /// Every character from the class, with its position.
+    Counted(Vec<(char, usize)>),
+    /// The first letter repeated with the right gap, and both its positions.
+    RepeatedLetter(Option<(char, usize, usize)>),
+    /// The first n-gram repeated without overlapping, and both its positions.
+    RepeatedNgram(Option<(String, usize, usize)>),
+    /// The first forbidden substring in the string, and its position.
+    Forbidden(Option<(String, usize)>),
+}
+
+/// A rule's judgement of a string, and why.
+#[derive(Debug, PartialEq)]
+struct Verdict<'a> {
+    rule: &'a Rule,
+    satisfied: bool,
+    evidence: Evidence,
+}
+
+impl Rule {
+    /// The same answer as `explain`, without gathering the evidence.
+    fn is_satisfied(&self, s: &str) -> bool {
+        match self {
+            Rule::MinCount { class, min } => {
+                s.chars().filter(|c| class.contains(*c)).count() >= *min
+            }
+            Rule::RepeatedLetter { gap } => s
+                .chars()
+                .zip(s.chars().skip(gap.saturating_add(1)))
+                .any(|(a, b)| a == b),
+            Rule::RepeatedNgram { len } => {
+                // Byte offsets of the characters, so that n-grams can be compared as slices.
+                let bounds: Vec<usize> =
+                    s.char_indices().map(|(i, _)| i).chain([s.len()]).collect();
+                let mut first_seen = HashMap::new();
+
+                (0..bounds.len().saturating_sub(*len)).any(|i| {
+                    let ngram = &s[bounds[i]..bounds[i + len]];
+                    let first = *first_seen.entry(ngram).or_insert(i);
+                    i - first >= *len
+                })
+            }
+            Rule::Forbidden(substrings) => !substrings.iter().any(|sub| s.contains(sub.as_str())),
+        }
+    }
+
+    fn explain(&self, s: &str) -> Verdict<'_> {
+        let chr_vec: Vec<char> = s.chars().collect();
+
+        let (satisfied, evidence) = match self {
+            Rule::MinCount { class, min } => {
+                let counted: Vec<(char, usize)> = chr_vec
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| class.contains(**c))
+                    .map(|(i, c)| (*c, i))
+                    .collect();
+
+                (counted.len() >= *min, Evidence::Counted(counted))
+            }
+            Rule::RepeatedLetter { gap } => {
+                let found = chr_vec
+                    .windows(gap + 2)
+                    .position(|win| win[0] == win[gap + 1])
+                    .map(|i| (chr_vec[i], i, i + gap + 1));
+
+                (found.is_some(), Evidence::RepeatedLetter(found))
+            }
+            Rule::RepeatedNgram { len } => {
+                let mut first_seen = HashMap::new();
+
+                let found = chr_vec.windows(*len).enumerate().find_map(|(i, ngram)| {
+                    let first = *first_seen.entry(ngram).or_insert(i);
+                    (i - first >= *len).then(|| (ngram.iter().collect(), first, i))
+                });
+
+                (found.is_some(), Evidence::RepeatedNgram(found))
+            }
+            Rule::Forbidden(substrings) => {
+                let found = substrings
+                    .iter()
+                    .filter_map(|sub| Some((sub.clone(), s.find(sub.as_str())?)))
+                    .min_by_key(|(_, pos)| *pos)
+                    .map(|(sub, pos)| (sub, s[..pos].chars().count()));
+
+                (found.is_none(), Evidence::Forbidden(found))
+            }
+        };
+
+        Verdict {
+            rule: self,
+            satisfied,
+            evidence,
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    /// Writes the rule the way `from_str` reads it.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::MinCount { class, min } => write!(f, "min-count {class} {min}"),
+            Rule::RepeatedLetter { gap } => write!(f, "repeated-letter {gap}"),
+            Rule::RepeatedNgram { len } => write!(f, "repeated-ngram {len}"),
+            Rule::Forbidden(substrings) => write!(f, "forbidden {}", substrings.join(" ")),
+        }
+    }
+}
+
+impl fmt::Display for Verdict<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mark = if self.satisfied { "ok" } else { "FAIL" };
+        write!(f, "[{mark}] {}: ", self.rule)?;
+
+        match &self.evidence {
+            Evidence::Counted(counted) if counted.is_empty() => write!(f, "counted none"),
+            Evidence::Counted(counted) => {
+                let list: Vec<String> =
+                    counted.iter().map(|(c, i)| format!("{c} at {i}")).collect();
+                write!(f, "counted {} ({})", counted.len(), list.join(", "))
+            }
+            Evidence::RepeatedLetter(Some((c, i, j))) => write!(f, "{c} at {i} and {j}"),
+            Evidence::RepeatedNgram(Some((ngram, i, j))) => write!(f, "{ngram} at {i} and {j}"),
+            Evidence::RepeatedLetter(None) | Evidence::RepeatedNgram(None) => {
+                write!(f, "no repeat")
+            }
+            Evidence::Forbidden(Some((sub, i))) => write!(f, "{sub} at {i}"),
+            Evidence::Forbidden(None) => write!(f, "none found"),
+        }
+    }
+}
 
-    let three_vowels = {
-        let mut three_vowels = false;
-        let mut n_vowels = 0;
+fn parse_number(s: Option<&str>) -> StdResult<usize, &'static str> {
+    s.ok_or(ERR)?.parse().map_err(|_| ERR)
+}
 
-        for c in &chr_vec {
-            if matches!(c, 'a' | 'e' | 'i' | 'o' | 'u') {
-                n_vowels += 1;
-                if n_vowels >= 3 {
-                    three_vowels = true;
-                    break;
-                }
+impl FromStr for Rule {
+    type Err = &'static str;
+
+    /// Parses one of:
+    ///
+    /// ```text
+    /// min-count CLASS MIN
+    /// repeated-letter GAP
+    /// repeated-ngram LEN
+    /// forbidden SUBSTRING...
+    /// ```
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        let mut spl = s.split_whitespace();
+        let kind = spl.next().ok_or(ERR)?;
+
+        let rule = match kind {
+            "min-count" => {
+                let class = spl.next().ok_or(ERR)?.to_string();
+                let min = parse_number(spl.next())?;
+                Rule::MinCount { class, min }
+            }
+            "repeated-letter" => {
+                let gap = parse_number(spl.next())?;
+                // Both letters and the gap have to fit in a window.
+                gap.checked_add(2).ok_or(ERR)?;
+                Rule::RepeatedLetter { gap }
             }
+            "repeated-ngram" => match parse_number(spl.next())? {
+                0 => return Err(ERR),
+                len => Rule::RepeatedNgram { len },
+            },
+            "forbidden" => Rule::Forbidden(spl.by_ref().map(String::from).collect()),
+            _ => return Err(ERR),
+        };
+
+        if spl.next().is_some() {
+            return Err(ERR);
         }
 
-        three_vowels
+        Ok(rule)
+    }
+}
+
+/// Rules which a string has to satisfy all of to be nice.
+#[derive(Debug, PartialEq)]
+struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    /// The rules from part one.
+    fn old_rules() -> Self {
+        Self {
+            rules: vec![
+                Rule::MinCount {
+                    class: "aeiou".to_string(),
+                    min: 3,
+                },
+                Rule::RepeatedLetter { gap: 0 },
+                Rule::Forbidden(["ab", "cd", "pq", "xy"].map(String::from).to_vec()),
+            ],
+        }
+    }
+
+    /// The rules from part two.
+    fn new_rules() -> Self {
+        Self {
+            rules: vec![
+                Rule::RepeatedNgram { len: 2 },
+                Rule::RepeatedLetter { gap: 1 },
+            ],
+        }
+    }
+
+    fn is_nice(&self, s: &str) -> bool {
+        self.rules.iter().all(|rule| rule.is_satisfied(s))
+    }
+
+    /// Every rule's verdict on the string, in order.
+    fn explain(&self, s: &str) -> Vec<Verdict<'_>> {
+        self.rules.iter().map(|rule| rule.explain(s)).collect()
+    }
+}
+
+impl FromStr for RuleSet {
+    type Err = &'static str;
+
+    /// Parses one rule per line. Blank lines and lines starting with `#` are skipped.
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        let rules = s
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(str::parse)
+            .collect::<StdResult<Vec<Rule>, _>>()?;
+
+        Ok(Self { rules })
+    }
+}
+
+/// The state of the built-in rules for the line being read by `count_nice`.
+#[derive(Default)]
+struct LineState {
+    /// Position of the next byte in the line.
+    pos: u32,
+    prev1: u8,
+    prev2: u8,
+    n_vowels: u32,
+    double_letter: bool,
+    forbidden_pair: bool,
+    repeated_pair: bool,
+    letter_with_gap: bool,
+}
+
+impl LineState {
+    fn is_nice_old(&self) -> bool {
+        self.n_vowels >= 3 && self.double_letter && !self.forbidden_pair
+    }
+
+    fn is_nice_new(&self) -> bool {
+        self.repeated_pair && self.letter_with_gap
+    }
+}
+
+/// Count the lines which are nice under the old and the new rules,
+/// checking both rule sets in a single pass over the bytes of the input.
+///
+/// This agrees with `RuleSet::old_rules` and `RuleSet::new_rules` on ASCII input.
+fn count_nice(input: &[u8]) -> (usize, usize) {
+    // Where each pair of bytes was first seen, tagged with the line it was seen on,
+    // so the table never has to be cleared between lines.
+    let mut first_pairs = vec![(0u32, 0u32); 1 << 16];
+    let mut line_id = 1;
+    let mut line = LineState::default();
+    let mut n_nice = (0, 0);
+
+    let mut finish_line = |line: &mut LineState, line_id: &mut u32| {
+        n_nice.0 += usize::from(line.is_nice_old());
+        n_nice.1 += usize::from(line.is_nice_new());
+        *line = LineState::default();
+        *line_id += 1;
     };
 
-    let one_letter_twice = chr_vec.windows(2).any(|win| win[0] == win[1]);
+    for (i, &b) in input.iter().enumerate() {
+        if b == b'\n' || (b == b'\r' && input.get(i + 1) == Some(&b'\n')) {
+            if b == b'\n' {
+                finish_line(&mut line, &mut line_id);
+            }
+            continue;
+        }
+
+        if matches!(b, b'a' | b'e' | b'i' | b'o' | b'u') {
+            line.n_vowels += 1;
+        }
 
-    let no_illegal_strings = chr_vec
-        .windows(2)
-        .all(|win| !matches!(win, ['a', 'b'] | ['c', 'd'] | ['p', 'q'] | ['x', 'y']));
+        if line.pos >= 1 {
+            line.double_letter |= b == line.prev1;
+            line.forbidden_pair |= matches!(
+                [line.prev1, b],
+                [b'a', b'b'] | [b'c', b'd'] | [b'p', b'q'] | [b'x', b'y']
+            );
+
+            let pair_pos = line.pos - 1;
+            let first = &mut first_pairs[usize::from(line.prev1) << 8 | usize::from(b)];
+            if first.0 == line_id {
+                line.repeated_pair |= pair_pos >= first.1 + 2;
+            } else {
+                *first = (line_id, pair_pos);
+            }
+        }
 
-    three_vowels && one_letter_twice && no_illegal_strings
+        if line.pos >= 2 {
+            line.letter_with_gap |= b == line.prev2;
+        }
+
+        line.prev2 = line.prev1;
+        line.prev1 = b;
+        line.pos += 1;
+    }
+
+    // Like `str::lines`, don't count an empty line after the last newline.
+    if line.pos > 0 {
+        finish_line(&mut line, &mut line_id);
+    }
+
+    n_nice
 }
 
-fn is_nice2(s: &str) -> bool {
-    // todo
-    false
+/// Print every rule's verdict on the given (1-based) lines of the input.
+fn explain(input: &str, line_numbers: &[usize], stdout: &mut impl Write) -> Result<()> {
+    let rule_sets = [
+        ("old rules", RuleSet::old_rules()),
+        ("new rules", RuleSet::new_rules()),
+    ];
+
+    for &line_number in line_numbers {
+        let line = input
+            .lines()
+            .nth(line_number.wrapping_sub(1))
+            .ok_or("no such line!")?;
+        writeln!(stdout, "Line {line_number}: {line}")?;
+
+        for (name, rules) in &rule_sets {
+            let verdicts = rules.explain(line);
+            let nice = verdicts.iter().all(|v| v.satisfied);
+            writeln!(
+                stdout,
+                "  {name}: {}",
+                if nice { "nice" } else { "naughty" }
+            )?;
+
+            for verdict in verdicts {
+                writeln!(stdout, "    {verdict}")?;
+            }
+        }
+    }
+
+    Ok(())
 }
 
 fn main() -> Result<()> {
     let input = fs::read_to_string("input.txt")?;
-    let mut n_nice1: usize = 0;
-    let mut n_nice2: usize = 0;
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    for line in input.lines() {
-        if is_nice1(line) {
-            n_nice1 += 1;
-        }
-        if is_nice2(line) {
-            n_nice2 += 1;
-        }
+    // `day05 explain LINE...` shows why the given lines are nice or naughty.
+    if args.first().is_some_and(|a| a == "explain") {
+        let line_numbers = args[1..]
+            .iter()
+            .map(|a| a.parse())
+            .collect::<StdResult<Vec<usize>, _>>()?;
+
+        return explain(&input, &line_numbers, &mut io::stdout().lock());
+    }
+
+    // `day05 RULES_FILE` counts the nice strings according to custom rules.
+    if let Some(path) = args.first() {
+        let rules: RuleSet = fs::read_to_string(path)?.parse()?;
+        let n_nice = input.lines().filter(|l| rules.is_nice(l)).count();
+
+        writeln!(
+            io::stdout(),
+            "There are {n_nice} nice string(s) according to the custom rules!"
+        )?;
+
+        return Ok(());
     }
 
+    let (n_nice1, n_nice2) = count_nice(input.as_bytes());
+
     writeln!(
         io::stdout(),
         "There are {n_nice1} nice string(s) according to the old rules!\n\
@@ -112,9 +471,18 @@ fn main() -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Instant;
 
     // Tests are from the puzzle description at the top.
 
+    fn is_nice1(s: &str) -> bool {
+        RuleSet::old_rules().is_nice(s)
+    }
+
+    fn is_nice2(s: &str) -> bool {
+        RuleSet::new_rules().is_nice(s)
+    }
+
     #[test]
     fn part1() {
         assert!(is_nice1("ugknbfddgicrmopn"));
@@ -125,6 +493,17 @@ mod tests {
         assert!(!is_nice1("dvszwmarrgswjxmb"));
     }
 
+    #[test]
+    fn count_nice_examples() {
+        let part1 = "ugknbfddgicrmopn\naaa\njchzalrnumimnmhp\nhaegwjzuvuyypxyu\ndvszwmarrgswjxmb";
+        let part2 = "qjhvhtzxzqqjkmpb\r\nxxyxx\r\nuurcxstgmygtbstg\r\nieodomkazucvgmuy\r\n";
+
+        assert_eq!(count_nice(part1.as_bytes()), (2, 0));
+        assert_eq!(count_nice(part2.as_bytes()), (0, 2));
+        assert_eq!(count_nice(b"aaaa\n\naaa"), (2, 1));
+        assert_eq!(count_nice(b""), (0, 0));
+    }
+
     #[test]
     fn part2() {
         assert!(is_nice2("qjhvhtzxzqqjkmpb"));
@@ -133,4 +512,197 @@ mod tests {
         assert!(!is_nice2("uurcxstgmygtbstg"));
         assert!(!is_nice2("ieodomkazucvgmuy"));
     }
+
+    #[test]
+    fn rules() {
+        assert!(Rule::RepeatedLetter { gap: 2 }.is_satisfied("abcad"));
+        assert!(!Rule::RepeatedLetter { gap: 2 }.is_satisfied("abcda"));
+        assert!(Rule::RepeatedNgram { len: 3 }.is_satisfied("abcxabc"));
+        assert!(!Rule::RepeatedNgram { len: 3 }.is_satisfied("abcabxbc"));
+        assert!(!Rule::RepeatedNgram { len: 2 }.is_satisfied("aaa"));
+        assert!(Rule::RepeatedNgram { len: 2 }.is_satisfied("aaaa"));
+    }
+
+    #[test]
+    fn is_satisfied_agrees_with_explain() {
+        let rules = [
+            "min-count aeiou 3",
+            "min-count xyz 0",
+            "repeated-letter 0",
+            "repeated-letter 1",
+            "repeated-letter 18446744073709551613",
+            "repeated-ngram 1",
+            "repeated-ngram 2",
+            "repeated-ngram 18446744073709551615",
+            "forbidden ab cd pq xy",
+            "forbidden",
+        ];
+
+        for rule in rules {
+            let rule: Rule = rule.parse().unwrap();
+            for s in [
+                "",
+                "a",
+                "aaa",
+                "aaaa",
+                "xyxy",
+                "haegwjzuvuyypxyu",
+                "ééaéé",
+                "qjhvhtzxzqqjkmpb",
+            ] {
+                assert_eq!(
+                    rule.is_satisfied(s),
+                    rule.explain(s).satisfied,
+                    "{rule} on {s}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn config() {
+        let config = "\
+            # Part one, spelled out.
+            min-count aeiou 3
+            repeated-letter 0
+
+            forbidden ab cd pq xy
+        ";
+        assert_eq!(config.parse::<RuleSet>().unwrap(), RuleSet::old_rules());
+
+        let config = "repeated-ngram 2\nrepeated-letter 1";
+        assert_eq!(config.parse::<RuleSet>().unwrap(), RuleSet::new_rules());
+
+        assert!("min-count aeiou".parse::<Rule>().is_err());
+        assert!("repeated-letter 1 2".parse::<Rule>().is_err());
+        assert!("repeated-ngram 0".parse::<Rule>().is_err());
+        assert!("repeated-letter 18446744073709551615"
+            .parse::<Rule>()
+            .is_err());
+        assert!("repeated-letter 18446744073709551613"
+            .parse::<Rule>()
+            .is_ok());
+        assert!("palindrome".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn explain_old_rules() {
+        let rules = RuleSet::old_rules();
+
+        let verdicts = rules.explain("haegwjzuvuyypxyu");
+        assert_eq!(
+            verdicts[0].evidence,
+            Evidence::Counted(vec![('a', 1), ('e', 2), ('u', 7), ('u', 9), ('u', 15)])
+        );
+        assert_eq!(
+            verdicts[1].evidence,
+            Evidence::RepeatedLetter(Some(('y', 10, 11)))
+        );
+        assert_eq!(
+            verdicts[2].evidence,
+            Evidence::Forbidden(Some(("xy".to_string(), 13)))
+        );
+        assert!(!verdicts[2].satisfied);
+
+        let verdicts = rules.explain("jchzalrnumimnmhp");
+        assert!(!verdicts[1].satisfied);
+        assert_eq!(
+            verdicts[1].to_string(),
+            "[FAIL] repeated-letter 0: no repeat"
+        );
+        assert_eq!(
+            verdicts[2].to_string(),
+            "[ok] forbidden ab cd pq xy: none found"
+        );
+    }
+
+    #[test]
+    fn explain_new_rules() {
+        let rules = RuleSet::new_rules();
+        let verdicts = rules.explain("qjhvhtzxzqqjkmpb");
+
+        assert_eq!(
+            verdicts[0].evidence,
+            Evidence::RepeatedNgram(Some(("qj".to_string(), 0, 10)))
+        );
+        assert_eq!(
+            verdicts[1].to_string(),
+            "[ok] repeated-letter 1: h at 2 and 4"
+        );
+    }
+
+    #[test]
+    fn explain_lines() {
+        let mut out = vec![];
+        explain("aaa\nxy", &[2], &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("Line 2: xy\n  old rules: naughty\n"));
+        assert!(out.contains("[FAIL] forbidden ab cd pq xy: xy at 0"));
+        assert!(explain("aaa", &[0], &mut vec![]).is_err());
+        assert!(explain("aaa", &[2], &mut vec![]).is_err());
+    }
+
+    /// Count nice lines with the rule engine, as the baseline for `count_nice`.
+    fn count_nice_rules(input: &str) -> (usize, usize) {
+        let old_rules = RuleSet::old_rules();
+        let new_rules = RuleSet::new_rules();
+
+        input.lines().fold((0, 0), |(n1, n2), line| {
+            (
+                n1 + usize::from(old_rules.is_nice(line)),
+                n2 + usize::from(new_rules.is_nice(line)),
+            )
+        })
+    }
+
+    /// `n_lines` words made by joining the front half of one line of `input`
+    /// to the back half of another, so they look like the puzzle's own strings.
+    fn word_list(input: &str, n_lines: usize) -> String {
+        let lines: Vec<&str> = input.lines().collect();
+        let mut list = String::with_capacity(n_lines * 17);
+
+        for k in 0..n_lines {
+            let front = lines[k % lines.len()];
+            let back = lines[(k / lines.len() + 7 * k) % lines.len()];
+            list.push_str(&front[..front.len() / 2]);
+            list.push_str(&back[back.len() / 2..]);
+            list.push('\n');
+        }
+
+        list
+    }
+
+    #[test]
+    fn count_nice_matches_rules() {
+        let input = fs::read_to_string("input.txt").unwrap();
+        assert_eq!(count_nice(input.as_bytes()), count_nice_rules(&input));
+
+        let list = word_list(&input, 10_000);
+        let (n_nice1, n_nice2) = count_nice(list.as_bytes());
+        assert!(n_nice1 > 0 && n_nice2 > 0);
+        assert_eq!((n_nice1, n_nice2), count_nice_rules(&list));
+    }
+
+    /// How much the single pass saves over the rule engine on several megabytes of words.
+    /// Build with `--release` for representative numbers and pass `--ignored --nocapture` to see them.
+    #[test]
+    #[ignore]
+    fn bench() {
+        let input = fs::read_to_string("input.txt").unwrap();
+        let list = word_list(&input, 500_000);
+        println!("{} MB of words", list.len() / 1_000_000);
+
+        let start = Instant::now();
+        let expected = count_nice_rules(&list);
+        let rules_time = start.elapsed();
+
+        let start = Instant::now();
+        let n_nice = count_nice(list.as_bytes());
+        let time = start.elapsed();
+
+        println!("{rules_time:?} (rule engine) -> {time:?} (single pass)");
+
+        assert_eq!(n_nice, expected);
+    }
 }
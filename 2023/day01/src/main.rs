@@ -58,55 +58,329 @@ In this example, the calibration values are 29, 83, 13, 24, 42, 14, and 76. Addi
 What is the sum of all of the calibration values?
 */
 
+use std::env;
 use std::error::Error;
+use std::fmt;
 use std::fs;
 use std::io::{self, Write};
 use std::result::Result as StdResult;
+use std::str::FromStr;
 
 type BoxErr = Box<dyn Error>;
 type Result<T> = StdResult<T, BoxErr>;
 
-fn recover_code1(s: &str) -> Result<usize> {
-    let mut iter = s.chars().filter(|x| x.is_ascii_digit());
+const VOCABULARY_ERR: &str = "invalid vocabulary!";
 
-    let digit1 = iter.next().ok_or::<BoxErr>("input error!".into())?;
-    let digit2 = iter.next_back().unwrap_or(digit1);
+/// Words which spell out digits, like `one` for 1.
+#[derive(Debug, Default, PartialEq)]
+struct Vocabulary {
+    words: Vec<(String, usize)>,
+}
+
+impl Vocabulary {
+    /// Build a vocabulary, rejecting empty words, words starting with a numeral,
+    /// digits above 9 and words which could be read as two different digits
+    /// because one is a prefix of the other.
+    fn new(words: Vec<(String, usize)>) -> Result<Self> {
+        for (i, (word, digit)) in words.iter().enumerate() {
+            if word.is_empty() || word.as_bytes()[0].is_ascii_digit() || *digit > 9 {
+                return Err(format!("{VOCABULARY_ERR} bad word: {word} {digit}").into());
+            }
+
+            for (other, other_digit) in &words[i + 1..] {
+                let is_prefix =
+                    word.starts_with(other.as_str()) || other.starts_with(word.as_str());
+                if is_prefix && digit != other_digit {
+                    return Err(format!("{VOCABULARY_ERR} ambiguous words: {word}, {other}").into());
+                }
+            }
+        }
+
+        Ok(Self { words })
+    }
+
+    fn from_list(words: &[&str]) -> Self {
+        Self {
+            words: (1..).zip(words).map(|(d, w)| (w.to_string(), d)).collect(),
+        }
+    }
+
+    /// The digits one to nine, spelled out.
+    fn english() -> Self {
+        Self::from_list(&[
+            "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+        ])
+    }
+
+    fn german() -> Self {
+        Self::from_list(&[
+            "eins", "zwei", "drei", "vier", "fünf", "sechs", "sieben", "acht", "neun",
+        ])
+    }
 
-    Ok(format!("{digit1}{digit2}").parse()?)
+    fn french() -> Self {
+        Self::from_list(&[
+            "un", "deux", "trois", "quatre", "cinq", "six", "sept", "huit", "neuf",
+        ])
+    }
+
+    fn spanish() -> Self {
+        Self::from_list(&[
+            "uno", "dos", "tres", "cuatro", "cinco", "seis", "siete", "ocho", "nueve",
+        ])
+    }
+
+    fn preset(name: &str) -> Option<Self> {
+        match name {
+            "english" => Some(Self::english()),
+            "german" => Some(Self::german()),
+            "french" => Some(Self::french()),
+            "spanish" => Some(Self::spanish()),
+            _ => None,
+        }
+    }
+
+    /// Pairs of words where the end of the first is the start of the second, like
+    /// `eight` and `two` in `eightwo`. Both are still found when they overlap.
+    fn overlaps(&self) -> Vec<(&str, &str)> {
+        let mut overlaps = vec![];
+
+        for (word, _) in &self.words {
+            for (other, _) in &self.words {
+                let overlap = (1..word.len().min(other.len()))
+                    .any(|n| word.as_bytes().ends_with(&other.as_bytes()[..n]));
+                if overlap {
+                    overlaps.push((word.as_str(), other.as_str()));
+                }
+            }
+        }
+
+        overlaps
+    }
+
+    /// The word at the start of `bytes` and the digit it spells out, if any.
+    fn match_start(&self, bytes: &[u8]) -> Option<&(String, usize)> {
+        self.words
+            .iter()
+            .find(|(word, _)| bytes.starts_with(word.as_bytes()))
+    }
 }
 
-fn recover_code2(s: &str) -> Result<usize> {
-    // Very stupid solution, but it works.
-    let s = s
-        .replace("one", "o1e")
-        .replace("two", "t2o")
-        .replace("three", "t3e")
-        .replace("four", "f4r")
-        .replace("five", "f5e")
-        .replace("six", "s6x")
-        .replace("seven", "s7n")
-        .replace("eight", "e8t")
-        .replace("nine", "n9e");
+impl FromStr for Vocabulary {
+    type Err = BoxErr;
+
+    /// Parses one `WORD DIGIT` pair per line. Blank lines and lines starting with `#` are skipped.
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        let words = s
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(|l| {
+                let (word, digit) = l.split_once(char::is_whitespace).ok_or(VOCABULARY_ERR)?;
+                Ok((word.to_string(), digit.trim().parse()?))
+            })
+            .collect::<Result<Vec<(String, usize)>>>()?;
+
+        Vocabulary::new(words)
+    }
+}
 
-    recover_code1(&s)
+/// A digit found in a line, either as a numeral or as a word from a vocabulary.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Token {
+    /// Byte position of the token's first character.
+    pos: usize,
+    /// Length of the token in bytes.
+    len: usize,
+    digit: usize,
 }
 
-fn main() -> Result<()> {
-    let input = fs::read_to_string("input.txt")?;
+/// Every digit token in `s`, in order.
+///
+/// A token is looked for at every position, so overlapping words
+/// like the `eight` and `two` in `eightwo` are both found.
+fn tokens<'a>(
+    s: &'a str,
+    vocabulary: &'a Vocabulary,
+) -> impl DoubleEndedIterator<Item = Token> + 'a {
+    let bytes = s.as_bytes();
+
+    (0..bytes.len()).filter_map(move |pos| {
+        let rest = &bytes[pos..];
+
+        let (digit, len) = if rest[0].is_ascii_digit() {
+            (usize::from(rest[0] - b'0'), 1)
+        } else {
+            let (word, digit) = vocabulary.match_start(rest)?;
+            (*digit, word.len())
+        };
+
+        Some(Token { pos, len, digit })
+    })
+}
 
-    let sum1: usize = input
+/// The first and last digit token in `s`, which are the same if there is only one.
+fn first_last(s: &str, vocabulary: &Vocabulary) -> Option<(Token, Token)> {
+    let mut iter = tokens(s, vocabulary);
+
+    let first = iter.next()?;
+    let last = iter.next_back().unwrap_or(first);
+
+    Some((first, last))
+}
+
+fn recover_code(s: &str, vocabulary: &Vocabulary) -> Result<usize> {
+    let (first, last) = first_last(s, vocabulary).ok_or::<BoxErr>("input error!".into())?;
+
+    Ok(first.digit * 10 + last.digit)
+}
+
+fn calibration_sum(input: &str, vocabulary: &Vocabulary) -> Result<usize> {
+    Ok(input
         .lines()
-        .map(recover_code1)
+        .enumerate()
+        .map(|(i, l)| {
+            recover_code(l, vocabulary).map_err(|e| format!("line {}: {e}", i + 1).into())
+        })
         .collect::<Result<Vec<usize>>>()?
         .into_iter()
-        .sum();
+        .sum())
+}
+
+/// What could be recovered from one line of the calibration document.
+struct Diagnosis<'a> {
+    line_number: usize,
+    line: &'a str,
+    first_last: Option<(Token, Token)>,
+}
+
+impl Diagnosis<'_> {
+    fn value(&self) -> Option<usize> {
+        self.first_last
+            .map(|(first, last)| first.digit * 10 + last.digit)
+    }
 
-    let sum2: usize = input
+    /// Does the line have only one digit, which makes up both halves of the value?
+    fn is_single_digit(&self) -> bool {
+        self.first_last
+            .is_some_and(|(first, last)| first.pos == last.pos)
+    }
+
+    fn token_text(&self, token: Token) -> &str {
+        &self.line[token.pos..token.pos + token.len]
+    }
+}
+
+impl fmt::Display for Diagnosis<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>5}: {}  ", self.line_number, self.line)?;
+
+        match self.first_last {
+            None => write!(f, "NO DIGITS"),
+            Some((first, last)) => {
+                write!(
+                    f,
+                    "first {} at {}, last {} at {} -> {}",
+                    self.token_text(first),
+                    first.pos,
+                    self.token_text(last),
+                    last.pos,
+                    first.digit * 10 + last.digit
+                )?;
+
+                if self.is_single_digit() {
+                    write!(f, "  SINGLE DIGIT")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+fn diagnose<'a>(input: &'a str, vocabulary: &Vocabulary) -> Vec<Diagnosis<'a>> {
+    input
         .lines()
-        .map(recover_code2)
-        .collect::<Result<Vec<usize>>>()?
-        .into_iter()
-        .sum();
+        .enumerate()
+        .map(|(i, line)| Diagnosis {
+            line_number: i + 1,
+            line,
+            first_last: first_last(line, vocabulary),
+        })
+        .collect()
+}
+
+/// List what was recovered from every line, flagging lines with no digits or
+/// only one, and sum up the values of the lines which have any digits.
+fn report(input: &str, vocabulary: &Vocabulary, stdout: &mut impl Write) -> Result<usize> {
+    let diagnoses = diagnose(input, vocabulary);
+
+    for diagnosis in &diagnoses {
+        writeln!(stdout, "{diagnosis}")?;
+    }
+
+    let sum: usize = diagnoses.iter().filter_map(Diagnosis::value).sum();
+    let n_valid = diagnoses.iter().filter(|d| d.first_last.is_some()).count();
+    let n_single = diagnoses.iter().filter(|d| d.is_single_digit()).count();
+
+    writeln!(
+        stdout,
+        "The sum of the calibration values of {n_valid} valid line(s) is {sum}\n\
+         {} line(s) have no digits, {n_single} line(s) have a single digit",
+        diagnoses.len() - n_valid
+    )?;
+
+    Ok(sum)
+}
+
+/// Get a preset vocabulary by its name, or read one from a file.
+fn load_vocabulary(name: &str) -> Result<Vocabulary> {
+    let vocabulary = match Vocabulary::preset(name) {
+        Some(vocabulary) => vocabulary,
+        None => fs::read_to_string(name)?.parse()?,
+    };
+
+    let overlaps: Vec<String> = vocabulary
+        .overlaps()
+        .iter()
+        .map(|(word, other)| format!("{word}/{other}"))
+        .collect();
+    if !overlaps.is_empty() {
+        eprintln!("Note: these words can overlap: {}", overlaps.join(", "));
+    }
+
+    Ok(vocabulary)
+}
+
+fn main() -> Result<()> {
+    let input = fs::read_to_string("input.txt")?;
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    // `day01 report [VOCABULARY]` diagnoses every line, using English words by default.
+    if args.first().is_some_and(|a| a == "report") {
+        let vocabulary = match args.get(1) {
+            Some(name) => load_vocabulary(name)?,
+            None => Vocabulary::english(),
+        };
+
+        report(&input, &vocabulary, &mut io::stdout().lock())?;
+        return Ok(());
+    }
+
+    // `day01 VOCABULARY` recovers the values with a preset's or a file's digit words.
+    if let Some(name) = args.first() {
+        let vocabulary = load_vocabulary(name)?;
+        let sum = calibration_sum(&input, &vocabulary)?;
+        writeln!(
+            io::stdout(),
+            "({name}) The sum of all the calibration values is {sum}"
+        )?;
+
+        return Ok(());
+    }
+
+    let sum1 = calibration_sum(&input, &Vocabulary::default())?;
+    let sum2 = calibration_sum(&input, &Vocabulary::english())?;
 
     writeln!(
         io::stdout(),
@@ -123,6 +397,14 @@ mod tests {
 
     // Tests are from the puzzle description at the top.
 
+    fn recover_code1(s: &str) -> Result<usize> {
+        recover_code(s, &Vocabulary::default())
+    }
+
+    fn recover_code2(s: &str) -> Result<usize> {
+        recover_code(s, &Vocabulary::english())
+    }
+
     #[test]
     fn part1() {
         let s = "1abc2";
@@ -161,4 +443,115 @@ mod tests {
         let s = "7pqrstsixteen";
         assert_eq!(recover_code2(s).unwrap(), 76);
     }
+
+    #[test]
+    fn overlapping_tokens() {
+        let digits: Vec<usize> = tokens("eightwone7", &Vocabulary::english())
+            .map(|t| t.digit)
+            .collect();
+        assert_eq!(digits, [8, 2, 1, 7]);
+
+        let (first, last) = first_last("xtwone3four", &Vocabulary::english()).unwrap();
+        assert_eq!(
+            first,
+            Token {
+                pos: 1,
+                len: 3,
+                digit: 2
+            }
+        );
+        assert_eq!(
+            last,
+            Token {
+                pos: 7,
+                len: 4,
+                digit: 4
+            }
+        );
+
+        let (first, last) = first_last("twone", &Vocabulary::english()).unwrap();
+        assert_eq!((first.digit, last.digit), (2, 1));
+
+        assert_eq!(first_last("twone", &Vocabulary::default()), None);
+    }
+
+    #[test]
+    fn vocabularies() {
+        let zero: Vocabulary = "# English, with zero\nzero 0\none 1\ntwo 2"
+            .parse()
+            .unwrap();
+        assert_eq!(recover_code("zerone", &zero).unwrap(), 1);
+        assert_eq!(recover_code("twozero", &zero).unwrap(), 20);
+
+        assert_eq!(
+            recover_code("zweiundvierzig", &Vocabulary::german()).unwrap(),
+            24
+        );
+        assert_eq!(recover_code("fünfx", &Vocabulary::german()).unwrap(), 55);
+        assert_eq!(
+            recover_code("deuxtroisquatre", &Vocabulary::french()).unwrap(),
+            24
+        );
+        assert_eq!(
+            recover_code("sietecuatro", &Vocabulary::spanish()).unwrap(),
+            74
+        );
+
+        for name in ["english", "german", "french", "spanish"] {
+            let preset = Vocabulary::preset(name).unwrap();
+            assert_eq!(Vocabulary::new(preset.words.clone()).unwrap(), preset);
+        }
+    }
+
+    #[test]
+    fn invalid_vocabularies() {
+        assert!("one 1\nonce 2".parse::<Vocabulary>().is_ok());
+        assert!("one 1\non 2".parse::<Vocabulary>().is_err());
+        assert!("one 1\none 2".parse::<Vocabulary>().is_err());
+        assert!("ten 10".parse::<Vocabulary>().is_err());
+        assert!("1st 1".parse::<Vocabulary>().is_err());
+        assert!("one".parse::<Vocabulary>().is_err());
+    }
+
+    #[test]
+    fn overlapping_words() {
+        let english = Vocabulary::english();
+        let overlaps = english.overlaps();
+
+        assert!(overlaps.contains(&("eight", "two")));
+        assert!(overlaps.contains(&("two", "one")));
+        assert!(!overlaps.contains(&("one", "two")));
+    }
+
+    #[test]
+    fn report_lines() {
+        let input = "two1nine\nabcdef\ntreb7uchet\nsixteen";
+        let mut out = vec![];
+        let sum = report(input, &Vocabulary::english(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert_eq!(sum, 29 + 77 + 66);
+        assert_eq!(
+            lines[0],
+            "    1: two1nine  first two at 0, last nine at 4 -> 29"
+        );
+        assert_eq!(lines[1], "    2: abcdef  NO DIGITS");
+        assert!(lines[2].ends_with("-> 77  SINGLE DIGIT"));
+        assert_eq!(
+            lines[3],
+            "    4: sixteen  first six at 0, last six at 0 -> 66  SINGLE DIGIT"
+        );
+        assert_eq!(
+            lines[5],
+            "1 line(s) have no digits, 2 line(s) have a single digit"
+        );
+        assert!(lines[4].contains("3 valid line(s) is 172"));
+    }
+
+    #[test]
+    fn sum_error_has_line_number() {
+        let err = calibration_sum("1abc2\nabc", &Vocabulary::default()).unwrap_err();
+        assert_eq!(err.to_string(), "line 2: input error!");
+    }
 }
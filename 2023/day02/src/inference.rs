@@ -0,0 +1,286 @@
+//! Inferring what was in the bag from the cubes the Elf showed.
+//!
+//! Each reveal is modelled as a handful drawn without replacement from the whole bag,
+//! with the cubes put back before the next one. Given the total number of cubes,
+//! every way of splitting them between the colors is equally likely a priori.
+//! Only the colors seen in the game are considered to be in the bag.
+
+use std::result::Result as StdResult;
+use std::str::FromStr;
+
+use crate::{ElfCubes, Game};
+
+const ERR: &str = "invalid prior!";
+
+/// The largest total a prior may give weight to.
+const MAX_TOTAL: usize = 10_000;
+
+/// The most bags a posterior is worked out over, to keep its memory use in check.
+const MAX_BAGS: u128 = 1_000_000;
+
+/// A prior distribution over the total number of cubes in the bag.
+#[derive(Debug, PartialEq)]
+pub struct Prior {
+    /// Natural log of the unnormalized weight of each total, indexed by the total.
+    /// Logs keep the weights of a prior with a large mean from underflowing.
+    ln_weights: Vec<f64>,
+}
+
+impl Prior {
+    /// Every total from `min` to `max` is equally likely.
+    /// Totals above `MAX_TOTAL` are left out.
+    pub fn uniform(min: usize, max: usize) -> Self {
+        let max = max.min(MAX_TOTAL);
+        Self {
+            ln_weights: (0..=max)
+                .map(|n| if n >= min { 0.0 } else { f64::NEG_INFINITY })
+                .collect(),
+        }
+    }
+
+    /// The total is Poisson distributed with the given mean,
+    /// cut off where the tail no longer matters or at `MAX_TOTAL`.
+    pub fn poisson(mean: f64) -> Self {
+        let max = poisson_cutoff(mean).min(MAX_TOTAL as f64) as usize;
+        let ln_mean = mean.ln();
+        let mut ln_weights = Vec::with_capacity(max + 1);
+        // `-mean + n * ln(mean) - ln(n!)`, updated from one `n` to the next.
+        let mut ln_weight = -mean;
+
+        for n in 0..=max {
+            ln_weights.push(ln_weight);
+            ln_weight += ln_mean - ((n + 1) as f64).ln();
+        }
+
+        Self { ln_weights }
+    }
+}
+
+fn poisson_cutoff(mean: f64) -> f64 {
+    mean + 10.0 * mean.sqrt() + 10.0
+}
+
+impl FromStr for Prior {
+    type Err = &'static str;
+
+    /// Parses `uniform:MIN-MAX` or `poisson:MEAN`. Priors reaching beyond `MAX_TOTAL` are rejected.
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        let (kind, arg) = s.split_once(':').ok_or(ERR)?;
+
+        match kind {
+            "uniform" => {
+                let (min, max) = arg.split_once('-').ok_or(ERR)?;
+                let min = min.parse().map_err(|_| ERR)?;
+                let max = max.parse().map_err(|_| ERR)?;
+
+                if min > max || max > MAX_TOTAL {
+                    return Err(ERR);
+                }
+
+                Ok(Prior::uniform(min, max))
+            }
+            "poisson" => match arg.parse() {
+                Ok(mean) if mean > 0.0 && poisson_cutoff(mean) <= MAX_TOTAL as f64 => {
+                    Ok(Prior::poisson(mean))
+                }
+                _ => Err(ERR),
+            },
+            _ => Err(ERR),
+        }
+    }
+}
+
+/// `ln(n!)` for every `n` up to some maximum.
+struct LnFactorials(Vec<f64>);
+
+impl LnFactorials {
+    fn up_to(max: usize) -> Self {
+        let mut ln_fact = vec![0.0; max + 1];
+
+        for n in 1..=max {
+            ln_fact[n] = ln_fact[n - 1] + (n as f64).ln();
+        }
+
+        Self(ln_fact)
+    }
+
+    /// `ln(n choose k)`, for `k <= n`.
+    fn ln_choose(&self, n: usize, k: usize) -> f64 {
+        self.0[n] - self.0[k] - self.0[n - k]
+    }
+}
+
+/// The posterior distribution of a bag's contents.
+#[derive(Debug)]
+pub struct Posterior {
+    colors: Vec<String>,
+    /// Every possible bag, as counts in the order of `colors`, with its probability.
+    bags: Vec<(Vec<usize>, f64)>,
+}
+
+impl Posterior {
+    /// Work out how likely every bag allowed by the prior is to have produced the game.
+    /// Fails if the prior rules out every bag, or allows too many to consider.
+    pub fn infer(game: &Game, prior: &Prior) -> StdResult<Self, &'static str> {
+        let minimal = game.minimal_set();
+        let colors: Vec<String> = minimal.counts.keys().cloned().collect();
+        let mins: Vec<usize> = minimal.counts.values().copied().collect();
+        let reveals: Vec<Vec<usize>> = game
+            .cube_sets
+            .iter()
+            .map(|cubes| colors.iter().map(|c| cubes.count(c)).collect())
+            .collect();
+
+        let max_total = prior.ln_weights.len().checked_sub(1).ok_or(NO_BAGS)?;
+        let ln_fact = LnFactorials::up_to(max_total + colors.len());
+
+        let mut bags = vec![];
+        let mut counts = mins.clone();
+        let min_total: usize = mins.iter().sum();
+
+        if min_total <= max_total {
+            if n_bags(max_total - min_total, colors.len()) > MAX_BAGS {
+                return Err("too many possible bags, try a narrower prior!");
+            }
+
+            enumerate_bags(&mut counts, 0, max_total - min_total, &mut |counts| {
+                let total: usize = counts.iter().sum();
+                let ln_weight = prior.ln_weights[total];
+                if ln_weight == f64::NEG_INFINITY {
+                    return;
+                }
+
+                // The prior of this split of the total, among all splits between the colors.
+                let n_splits = ln_fact.ln_choose(total + colors.len() - 1, colors.len() - 1);
+                let mut ln_p = ln_weight - n_splits;
+
+                for reveal in &reveals {
+                    let handful: usize = reveal.iter().sum();
+                    ln_p -= ln_fact.ln_choose(total, handful);
+                    for (&c, &r) in counts.iter().zip(reveal) {
+                        ln_p += ln_fact.ln_choose(c, r);
+                    }
+                }
+
+                bags.push((counts.to_vec(), ln_p));
+            });
+        }
+
+        // Normalize in log space first, so that tiny likelihoods don't underflow.
+        let max_ln_p = bags
+            .iter()
+            .map(|(_, p)| *p)
+            .fold(f64::NEG_INFINITY, f64::max);
+        if max_ln_p == f64::NEG_INFINITY {
+            return Err(NO_BAGS);
+        }
+
+        let sum: f64 = bags.iter().map(|(_, p)| (p - max_ln_p).exp()).sum();
+        for (_, p) in &mut bags {
+            *p = (*p - max_ln_p).exp() / sum;
+        }
+
+        Ok(Self { colors, bags })
+    }
+
+    /// The single most probable bag.
+    pub fn most_likely(&self) -> (ElfCubes, f64) {
+        let (counts, p) = self
+            .bags
+            .iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("a posterior has at least one bag");
+
+        (self.cubes(counts), *p)
+    }
+
+    /// The probability of each count of `color`, indexed by the count.
+    pub fn marginal(&self, color: &str) -> Vec<f64> {
+        let Some(i) = self.colors.iter().position(|c| c == color) else {
+            return vec![1.0];
+        };
+        let max = self.bags.iter().map(|(c, _)| c[i]).max().unwrap_or(0);
+        let mut marginal = vec![0.0; max + 1];
+
+        for (counts, p) in &self.bags {
+            marginal[counts[i]] += p;
+        }
+
+        marginal
+    }
+
+    pub fn mean(&self, color: &str) -> f64 {
+        self.marginal(color)
+            .iter()
+            .enumerate()
+            .map(|(n, p)| n as f64 * p)
+            .sum()
+    }
+
+    /// The equal-tailed interval which holds the count of `color` with probability `mass`.
+    pub fn credible_interval(&self, color: &str, mass: f64) -> (usize, usize) {
+        let tail = (1.0 - mass) / 2.0;
+        let cdf: Vec<f64> = self
+            .marginal(color)
+            .iter()
+            .scan(0.0, |acc, p| {
+                *acc += p;
+                Some(*acc)
+            })
+            .collect();
+
+        let low = cdf.iter().position(|&c| c > tail).unwrap_or(0);
+        // Allow for rounding errors when the upper tail is empty.
+        let high = cdf
+            .iter()
+            .position(|&c| c >= 1.0 - tail - 1e-12)
+            .unwrap_or(cdf.len() - 1);
+
+        (low, high)
+    }
+
+    pub fn colors(&self) -> &[String] {
+        &self.colors
+    }
+
+    fn cubes(&self, counts: &[usize]) -> ElfCubes {
+        ElfCubes::new(
+            self.colors
+                .iter()
+                .map(String::as_str)
+                .zip(counts.iter().copied()),
+        )
+    }
+}
+
+const NO_BAGS: &str = "the prior rules out every bag!";
+
+/// The number of ways of adding at most `spare` cubes to `n_colors` colors, up to `MAX_BAGS + 1`.
+fn n_bags(spare: usize, n_colors: usize) -> u128 {
+    let mut n = 1u128;
+
+    // After step `i`, this is `(spare + i) choose i`.
+    for i in 1..=n_colors as u128 {
+        n = n * (spare as u128 + i) / i;
+        if n > MAX_BAGS {
+            return MAX_BAGS + 1;
+        }
+    }
+
+    n
+}
+
+/// Call `f` with every way of adding at most `spare` cubes to `counts[i..]`.
+fn enumerate_bags(counts: &mut [usize], i: usize, spare: usize, f: &mut impl FnMut(&[usize])) {
+    if i == counts.len() {
+        f(counts);
+        return;
+    }
+
+    let min = counts[i];
+    for extra in 0..=spare {
+        counts[i] = min + extra;
+        enumerate_bags(counts, i + 1, spare - extra, f);
+    }
+    counts[i] = min;
+}
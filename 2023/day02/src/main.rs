@@ -80,35 +80,52 @@ For each game, find the minimum set of cubes that must have been present.
 What is the sum of the power of these sets?
 */
 
+mod inference;
+mod simulator;
+
+use std::collections::BTreeMap;
+use std::env;
 use std::error::Error;
+use std::fmt;
 use std::fs;
 use std::io::{self, Write};
 use std::result::Result as StdResult;
 use std::str::FromStr;
 
+use inference::{Posterior, Prior};
+
 type BoxErr = Box<dyn Error>;
 type Result<T> = StdResult<T, BoxErr>;
 
-#[derive(Clone, Copy)]
+/// How many cubes of each color there are in a set.
+#[derive(Clone, Debug, Default, PartialEq)]
 struct ElfCubes {
-    red: usize,
-    green: usize,
-    blue: usize,
+    counts: BTreeMap<String, usize>,
 }
 
-const FULL_SET: ElfCubes = ElfCubes::new(12, 13, 14);
+/// The bag from part one.
+fn full_set() -> ElfCubes {
+    ElfCubes::new([("red", 12), ("green", 13), ("blue", 14)])
+}
 
 impl ElfCubes {
-    pub const fn new(red: usize, green: usize, blue: usize) -> Self {
-        ElfCubes { red, green, blue }
+    pub fn new<'a>(counts: impl IntoIterator<Item = (&'a str, usize)>) -> Self {
+        ElfCubes {
+            counts: counts
+                .into_iter()
+                .map(|(color, n)| (color.to_string(), n))
+                .collect(),
+        }
     }
 
-    pub fn is_possible(self, full_set: ElfCubes) -> bool {
-        self.red <= full_set.red && self.green <= full_set.green && self.blue <= full_set.blue
+    /// How many cubes of `color` there are, 0 if the color isn't in the set.
+    pub fn count(&self, color: &str) -> usize {
+        self.counts.get(color).copied().unwrap_or(0)
     }
 
-    pub fn power(self) -> usize {
-        self.red * self.green * self.blue
+    /// The product of the counts of all colors in the set.
+    pub fn power(&self) -> usize {
+        self.counts.values().product()
     }
 }
 
@@ -117,8 +134,10 @@ const ERR: &str = "input error!";
 impl FromStr for ElfCubes {
     type Err = BoxErr;
 
+    /// Parses e.g. `3 blue, 4 red`. Every color has to be listed
+    /// at most once, with at least one cube.
     fn from_str(s: &str) -> StdResult<Self, Self::Err> {
-        let mut cubes = ElfCubes::new(0, 0, 0);
+        let mut cubes = ElfCubes::default();
 
         let spl = s.split(", ");
 
@@ -127,11 +146,14 @@ impl FromStr for ElfCubes {
             let n = spl2.next().ok_or(ERR)?.parse()?;
             let color = spl2.next().ok_or(ERR)?;
 
-            match color {
-                "red" => cubes.red = n,
-                "green" => cubes.green = n,
-                "blue" => cubes.blue = n,
-                _ => return Err(ERR.into()),
+            if spl2.next().is_some() {
+                return Err(ERR.into());
+            }
+            if n == 0 {
+                return Err(format!("{ERR} no {color} cubes listed").into());
+            }
+            if cubes.counts.insert(color.to_string(), n).is_some() {
+                return Err(format!("{ERR} {color} listed twice").into());
             }
         }
 
@@ -139,6 +161,44 @@ impl FromStr for ElfCubes {
     }
 }
 
+impl fmt::Display for ElfCubes {
+    /// Writes the set the way `from_str` reads it, with the colors in alphabetical order.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (color, n)) in self.counts.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{n} {color}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A color in one of a game's reveals with more cubes than the bag holds.
+#[derive(Debug, PartialEq)]
+struct Violation {
+    /// Index of the reveal in the game, starting at 0.
+    reveal: usize,
+    color: String,
+    seen: usize,
+    limit: usize,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "reveal {} showed {} {}, but the bag holds only {}",
+            self.reveal + 1,
+            self.seen,
+            self.color,
+            self.limit
+        )
+    }
+}
+
+#[derive(Debug, PartialEq)]
 struct Game {
     id: usize,
     cube_sets: Vec<ElfCubes>,
@@ -149,16 +209,49 @@ impl Game {
         Self { id, cube_sets }
     }
 
-    pub fn is_possible(&self, full_set: ElfCubes) -> bool {
-        self.cube_sets.iter().all(|x| x.is_possible(full_set))
+    pub fn is_possible(&self, full_set: &ElfCubes) -> bool {
+        self.first_violation(full_set).is_none()
+    }
+
+    /// Every color in every reveal which the bag doesn't hold enough cubes of,
+    /// in the order they appear. Empty if the game is possible.
+    pub fn violations<'a>(
+        &'a self,
+        full_set: &'a ElfCubes,
+    ) -> impl Iterator<Item = Violation> + 'a {
+        self.cube_sets
+            .iter()
+            .enumerate()
+            .flat_map(move |(reveal, cubes)| {
+                cubes.counts.iter().filter_map(move |(color, &seen)| {
+                    let limit = full_set.count(color);
+                    (seen > limit).then(|| Violation {
+                        reveal,
+                        color: color.clone(),
+                        seen,
+                        limit,
+                    })
+                })
+            })
+    }
+
+    /// The first reason the game is impossible with `full_set`, if it is.
+    pub fn first_violation(&self, full_set: &ElfCubes) -> Option<Violation> {
+        self.violations(full_set).next()
     }
 
+    /// The fewest cubes of each color seen in the game that the bag could have held.
     pub fn minimal_set(&self) -> ElfCubes {
-        let max_red = self.cube_sets.iter().map(|x| x.red).max().unwrap();
-        let max_green = self.cube_sets.iter().map(|x| x.green).max().unwrap();
-        let max_blue = self.cube_sets.iter().map(|x| x.blue).max().unwrap();
+        let mut minimal = ElfCubes::default();
 
-        ElfCubes::new(max_red, max_green, max_blue)
+        for cubes in &self.cube_sets {
+            for (color, &n) in &cubes.counts {
+                let max = minimal.counts.entry(color.clone()).or_insert(0);
+                *max = (*max).max(n);
+            }
+        }
+
+        minimal
     }
 }
 
@@ -190,17 +283,148 @@ impl FromStr for Game {
     }
 }
 
+impl fmt::Display for Game {
+    /// Writes the game the way `from_str` reads it.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Game {}: ", self.id)?;
+
+        for (i, cubes) in self.cube_sets.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{cubes}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The sum of the IDs of the games which are possible with `bag`.
+fn possible_id_sum(games: &[Game], bag: &ElfCubes) -> usize {
+    games
+        .iter()
+        .filter(|g| g.is_possible(bag))
+        .map(|g| g.id)
+        .sum()
+}
+
+/// Read one bag per line, like `12 red, 13 green, 14 blue`.
+/// Blank lines and lines starting with `#` are skipped.
+fn parse_bags(s: &str) -> Result<Vec<(String, ElfCubes)>> {
+    s.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| Ok((l.to_string(), l.parse()?)))
+        .collect()
+}
+
+/// List every game which is impossible with `bag`, with all the reasons why.
+fn explain(games: &[Game], bag: &ElfCubes, stdout: &mut impl Write) -> Result<()> {
+    for game in games {
+        let violations: Vec<Violation> = game.violations(bag).collect();
+
+        if !violations.is_empty() {
+            writeln!(stdout, "Game {} is impossible:", game.id)?;
+        }
+        for violation in violations {
+            writeln!(stdout, "  {violation}")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Report the posterior of the bag's contents for one game.
+fn infer(game: &Game, prior: &Prior, stdout: &mut impl Write) -> Result<()> {
+    let posterior = Posterior::infer(game, prior)?;
+    let (bag, p) = posterior.most_likely();
+
+    writeln!(
+        stdout,
+        "Game {}: most likely bag is {bag} (p = {p:.4})",
+        game.id
+    )?;
+
+    for color in posterior.colors() {
+        let (low, high) = posterior.credible_interval(color, 0.95);
+        writeln!(
+            stdout,
+            "  {color}: mean {:.2}, 95% credible interval {low}-{high}",
+            posterior.mean(color)
+        )?;
+    }
+
+    Ok(())
+}
+
 fn main() -> Result<()> {
     let input = fs::read_to_string("input.txt")?;
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    let mut games = input
+    let games = input
         .lines()
         .map(|l| l.parse())
         .collect::<Result<Vec<Game>>>()?;
 
+    // `day02 --explain [BAG]` lists why games are impossible, with the part one bag by default.
+    if args.first().is_some_and(|a| a == "--explain") {
+        let bag = match args.get(1) {
+            Some(bag) => bag.parse()?,
+            None => full_set(),
+        };
+
+        return explain(&games, &bag, &mut io::stdout().lock());
+    }
+
+    // `day02 --simulate BAG GAMES REVEALS SEED` prints records of games played with BAG.
+    if args.first().is_some_and(|a| a == "--simulate") {
+        let [_, bag, n_games, n_reveals, seed] = args.as_slice() else {
+            return Err("usage: day02 --simulate BAG GAMES REVEALS SEED".into());
+        };
+        let mut stdout = io::stdout().lock();
+        let records = simulator::simulate(
+            &bag.parse()?,
+            n_games.parse()?,
+            n_reveals.parse()?,
+            seed.parse()?,
+        );
+
+        for record in records {
+            writeln!(stdout, "{record}")?;
+        }
+
+        return Ok(());
+    }
+
+    // `day02 --infer ID [PRIOR]` infers what was in the bag during a game.
+    if args.first().is_some_and(|a| a == "--infer") {
+        let id: usize = args.get(1).ok_or("missing game ID!")?.parse()?;
+        let game = games.iter().find(|g| g.id == id).ok_or("no such game!")?;
+        let prior = args
+            .get(2)
+            .map_or("uniform:0-100", |p| p.as_str())
+            .parse()?;
+
+        return infer(game, &prior, &mut io::stdout().lock());
+    }
+
+    // `day02 BAG...` or `day02 --bags FILE` sums up the IDs of the games possible with each bag.
+    if !args.is_empty() {
+        let bags = match args.as_slice() {
+            [flag, path] if flag == "--bags" => parse_bags(&fs::read_to_string(path)?)?,
+            _ => parse_bags(&args.join("\n"))?,
+        };
+        let mut stdout = io::stdout().lock();
+
+        for (name, bag) in bags {
+            writeln!(stdout, "{name}: {}", possible_id_sum(&games, &bag))?;
+        }
+
+        return Ok(());
+    }
+
+    let sum1 = possible_id_sum(&games, &full_set());
     let sum2: usize = games.iter().map(|g| g.minimal_set().power()).sum();
-    games.retain(|g| g.is_possible(FULL_SET));
-    let sum1: usize = games.iter().map(|x| x.id).sum();
 
     writeln!(
         io::stdout(),
@@ -232,11 +456,11 @@ mod tests {
         .unwrap();
         let g5 = Game::from_str("Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green").unwrap();
 
-        assert!(g1.is_possible(FULL_SET));
-        assert!(g2.is_possible(FULL_SET));
-        assert!(!g3.is_possible(FULL_SET));
-        assert!(!g4.is_possible(FULL_SET));
-        assert!(g5.is_possible(FULL_SET));
+        assert!(g1.is_possible(&full_set()));
+        assert!(g2.is_possible(&full_set()));
+        assert!(!g3.is_possible(&full_set()));
+        assert!(!g4.is_possible(&full_set()));
+        assert!(g5.is_possible(&full_set()));
 
         assert_eq!(g1.id + g2.id + g3.id + g4.id + g5.id, 15);
     }
@@ -262,4 +486,228 @@ mod tests {
         assert_eq!(g4.minimal_set().power(), 630);
         assert_eq!(g5.minimal_set().power(), 36);
     }
+
+    #[test]
+    fn other_colors() {
+        let g = Game::from_str("Game 7: 2 yellow, 1 red; 5 purple, 3 yellow; 1 red").unwrap();
+
+        assert_eq!(
+            g.minimal_set(),
+            ElfCubes::new([("purple", 5), ("red", 1), ("yellow", 3)])
+        );
+        assert_eq!(g.minimal_set().power(), 15);
+
+        let bag = ElfCubes::new([("red", 1), ("yellow", 3), ("purple", 5)]);
+        assert!(g.is_possible(&bag));
+        assert!(!g.is_possible(&full_set()));
+
+        assert!(ElfCubes::from_str("2 dark red").is_err());
+    }
+
+    #[test]
+    fn bags() {
+        let games = [
+            "Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green",
+            "Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue",
+            "Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red",
+        ]
+        .map(|g| g.parse().unwrap());
+
+        let bags = parse_bags(
+            "# the puzzle's bag\n12 red, 13 green, 14 blue\n\n20 red, 13 green, 6 blue\n1 red",
+        )
+        .unwrap();
+        let sums: Vec<usize> = bags
+            .iter()
+            .map(|(_, bag)| possible_id_sum(&games, bag))
+            .collect();
+
+        assert_eq!(bags[0].0, "12 red, 13 green, 14 blue");
+        assert_eq!(sums, [3, 6, 0]);
+        assert!(parse_bags("12 red, lots of blue").is_err());
+    }
+
+    #[test]
+    fn violations() {
+        let g3 = Game::from_str(
+            "Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red",
+        )
+        .unwrap();
+        let g5 = Game::from_str("Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green").unwrap();
+
+        assert_eq!(
+            g3.first_violation(&full_set()),
+            Some(Violation {
+                reveal: 0,
+                color: "red".to_string(),
+                seen: 20,
+                limit: 12
+            })
+        );
+        assert_eq!(g3.violations(&full_set()).count(), 1);
+        assert_eq!(g5.first_violation(&full_set()), None);
+
+        let small_bag = ElfCubes::new([("red", 5), ("green", 5), ("blue", 5)]);
+        let violations: Vec<String> = g3.violations(&small_bag).map(|v| v.to_string()).collect();
+        assert_eq!(
+            violations,
+            [
+                "reveal 1 showed 6 blue, but the bag holds only 5",
+                "reveal 1 showed 8 green, but the bag holds only 5",
+                "reveal 1 showed 20 red, but the bag holds only 5",
+                "reveal 2 showed 13 green, but the bag holds only 5",
+            ]
+        );
+
+        let mut out = vec![];
+        explain(&[g3, g5], &full_set(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Game 3 is impossible:\n  reveal 1 showed 20 red, but the bag holds only 12\n"
+        );
+    }
+
+    #[test]
+    fn posterior_single_color() {
+        // With only red cubes seen, the bag is all red, so only the prior matters.
+        let game = Game::from_str("Game 1: 3 red; 1 red").unwrap();
+        let posterior = Posterior::infer(&game, &Prior::uniform(0, 10)).unwrap();
+
+        let marginal = posterior.marginal("red");
+        assert!(marginal[..3].iter().all(|&p| p == 0.0));
+        assert!(marginal[3..].iter().all(|&p| (p - 1.0 / 8.0).abs() < 1e-9));
+        assert_eq!(posterior.credible_interval("red", 1.0), (3, 10));
+    }
+
+    #[test]
+    fn posterior_two_colors() {
+        // From 3 cubes, (1 red, 2 blue) has likelihood 1/3 * 1/3 * 2/3 = 2/27
+        // and (2 red, 1 blue) has 2/3 * 2/3 * 1/3 = 4/27.
+        let game = Game::from_str("Game 1: 1 red; 1 red; 1 blue").unwrap();
+        let posterior = Posterior::infer(&game, &"uniform:3-3".parse().unwrap()).unwrap();
+
+        let (bag, p) = posterior.most_likely();
+        assert_eq!(bag, ElfCubes::new([("red", 2), ("blue", 1)]));
+        assert!((p - 2.0 / 3.0).abs() < 1e-9);
+        assert!((posterior.mean("blue") - 4.0 / 3.0).abs() < 1e-9);
+        assert_eq!(posterior.credible_interval("red", 0.5), (1, 2));
+    }
+
+    #[test]
+    fn posterior_respects_minimal_set() {
+        let game = Game::from_str(
+            "Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red",
+        )
+        .unwrap();
+        let minimal = game.minimal_set();
+        let posterior = Posterior::infer(&game, &"poisson:40".parse().unwrap()).unwrap();
+
+        let (bag, _) = posterior.most_likely();
+        for color in posterior.colors() {
+            assert!(bag.count(color) >= minimal.count(color));
+            assert!(posterior.credible_interval(color, 0.95).0 >= minimal.count(color));
+        }
+
+        assert_eq!(
+            Posterior::infer(&game, &Prior::uniform(0, 31)).unwrap_err(),
+            "the prior rules out every bag!"
+        );
+        assert!("uniform:5-1".parse::<Prior>().is_err());
+        assert!("poisson:-3".parse::<Prior>().is_err());
+        assert!("poisson:inf".parse::<Prior>().is_err());
+        assert!("poisson:NaN".parse::<Prior>().is_err());
+        assert!("poisson:1e300".parse::<Prior>().is_err());
+        assert!("uniform:0-18446744073709551615".parse::<Prior>().is_err());
+    }
+
+    #[test]
+    fn large_poisson_mean() {
+        // `exp(-mean)` alone would underflow to 0 for these means.
+        let game: Game = "Game 1: 3 red".parse().unwrap();
+        let posterior = Posterior::infer(&game, &"poisson:1000".parse().unwrap()).unwrap();
+        assert!((posterior.mean("red") - 1000.0).abs() < 5.0);
+
+        let game: Game = "Game 2: 3 red, 2 blue; 1 blue".parse().unwrap();
+        let posterior = Posterior::infer(&game, &"poisson:800".parse().unwrap()).unwrap();
+        let total = posterior.mean("red") + posterior.mean("blue");
+        assert!((total - 800.0).abs() < 10.0);
+    }
+
+    #[test]
+    fn too_many_bags() {
+        let game: Game = "Game 1: 1 red, 1 green, 1 blue, 1 yellow, 1 purple, 1 white"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            Posterior::infer(&game, &"uniform:0-100".parse().unwrap()).unwrap_err(),
+            "too many possible bags, try a narrower prior!"
+        );
+        assert!(Posterior::infer(&game, &"uniform:0-20".parse().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn simulated_games() {
+        let bag = ElfCubes::new([("red", 12), ("green", 13), ("blue", 14), ("yellow", 2)]);
+        let records = simulator::simulate(&bag, 200, 6, 42);
+
+        assert_eq!(records.len(), 200);
+        assert_eq!(records, simulator::simulate(&bag, 200, 6, 42));
+        assert_ne!(records, simulator::simulate(&bag, 200, 6, 43));
+
+        for (i, record) in records.iter().enumerate() {
+            let game = Game::from_str(record).unwrap();
+            assert_eq!(game.id, i + 1);
+            assert_eq!(game.cube_sets.len(), 6);
+            assert!(game.is_possible(&bag));
+
+            for (color, &n) in &game.minimal_set().counts {
+                assert!(n <= bag.count(color));
+            }
+        }
+
+        assert!(simulator::simulate(&ElfCubes::default(), 5, 3, 1).is_empty());
+        assert!(simulator::simulate(&bag, 5, 0, 1).is_empty());
+
+        let records = simulator::simulate(&ElfCubes::new([("red", 3), ("blue", 0)]), 50, 2, 7);
+        assert!(records.iter().all(|r| !r.contains("blue")));
+
+        // The bag is never laid out cube by cube.
+        let big = ElfCubes::new([("red", 1_000_000), ("green", 3), ("blue", 500_000)]);
+        for record in simulator::simulate(&big, 3, 3, 5) {
+            let game: Game = record.parse().unwrap();
+            assert!(game.is_possible(&big));
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let input = fs::read_to_string("input.txt").unwrap();
+
+        for line in input.lines() {
+            let game = Game::from_str(line).unwrap();
+            let canonical = game.to_string();
+
+            assert_eq!(Game::from_str(&canonical).unwrap(), game);
+            assert_eq!(Game::from_str(&canonical).unwrap().to_string(), canonical);
+        }
+
+        let game =
+            Game::from_str("Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green").unwrap();
+        assert_eq!(
+            game.to_string(),
+            "Game 1: 3 blue, 4 red; 6 blue, 2 green, 1 red; 2 green"
+        );
+    }
+
+    #[test]
+    fn invalid_reveals() {
+        let err = ElfCubes::from_str("3 red, 4 red").unwrap_err();
+        assert_eq!(err.to_string(), "input error! red listed twice");
+
+        let err = ElfCubes::from_str("3 red, 0 blue").unwrap_err();
+        assert_eq!(err.to_string(), "input error! no blue cubes listed");
+
+        assert!(Game::from_str("Game 1: 3 red; 1 blue, 2 blue").is_err());
+    }
 }
@@ -0,0 +1,92 @@
+//! Playing the cube game with a known bag, to produce records to test against.
+
+use crate::ElfCubes;
+
+/// A small, seedable pseudo-random number generator (SplitMix64).
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`. The bias from the modulo is negligible for small `n`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// Records of games played with `bag`, in the format `Game::from_str` reads.
+///
+/// For every reveal, the Elf grabs a handful of a random size from the whole bag,
+/// shows it and puts the cubes back. There are no records without cubes or reveals.
+/// Cubes are drawn from the counts of each color, so a huge bag takes no more memory
+/// than a small one, though the time to draw a handful still grows with the bag.
+pub fn simulate(bag: &ElfCubes, n_games: usize, n_reveals: usize, seed: u64) -> Vec<String> {
+    let mut rng = Rng(seed);
+    let colors: Vec<(&str, usize)> = bag
+        .counts
+        .iter()
+        .filter(|(_, &n)| n > 0)
+        .map(|(color, &n)| (color.as_str(), n))
+        .collect();
+    let total: usize = colors.iter().map(|(_, n)| n).sum();
+
+    if total == 0 || n_reveals == 0 {
+        return vec![];
+    }
+
+    (1..=n_games)
+        .map(|id| {
+            let reveals: Vec<String> = (0..n_reveals)
+                .map(|_| {
+                    let handful_size = 1 + rng.below(total);
+                    let mut left: Vec<usize> = colors.iter().map(|(_, n)| *n).collect();
+
+                    // Draw whichever is smaller, the handful or the cubes staying in the bag.
+                    let n_draws = handful_size.min(total - handful_size);
+                    let mut drawn = vec![0; colors.len()];
+
+                    // Each remaining cube is equally likely to be drawn next,
+                    // so each color is as likely as its remaining count.
+                    for n_left in (total - n_draws + 1..=total).rev() {
+                        let mut k = rng.below(n_left);
+                        let i = left
+                            .iter()
+                            .position(|&n| {
+                                if k < n {
+                                    return true;
+                                }
+                                k -= n;
+                                false
+                            })
+                            .expect("the remaining counts add up to n_left");
+
+                        left[i] -= 1;
+                        drawn[i] += 1;
+                    }
+
+                    if n_draws < handful_size {
+                        drawn = left;
+                    }
+
+                    let handful = ElfCubes::new(
+                        colors
+                            .iter()
+                            .zip(drawn)
+                            .filter(|(_, n)| *n > 0)
+                            .map(|((color, _), n)| (*color, n)),
+                    );
+
+                    handful.to_string()
+                })
+                .collect();
+
+            format!("Game {id}: {}", reveals.join("; "))
+        })
+        .collect()
+}
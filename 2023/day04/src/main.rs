@@ -97,9 +97,12 @@ Process all of the original and copied scratchcards until no more scratchcards a
 Including the original set of scratchcards, how many total scratchcards do you end up with?
 */
 
+use std::env;
 use std::error::Error;
+use std::fmt;
 use std::fs;
 use std::io::{self, Write};
+use std::ops::Range;
 use std::result::Result as StdResult;
 use std::str::FromStr;
 
@@ -107,75 +110,455 @@ type BoxErr = Box<dyn Error>;
 type Result<T> = StdResult<T, BoxErr>;
 
 struct Card {
+    id: usize,
+    /// Sorted, so that they can be searched quickly.
     winning_numbers: Vec<usize>,
     numbers: Vec<usize>,
 }
 
 impl Card {
-    fn new(winning_numbers: Vec<usize>, numbers: Vec<usize>) -> Self {
+    fn new(id: usize, mut winning_numbers: Vec<usize>, numbers: Vec<usize>) -> Self {
+        winning_numbers.sort_unstable();
+
         Self {
+            id,
             winning_numbers,
             numbers,
         }
     }
 
-    fn points(&self) -> usize {
-        let mut n_wins = 0;
-
-        for num in &self.numbers {
-            if self.winning_numbers.contains(num) {
-                n_wins += 1;
-            }
+    /// How many of the numbers you have are winning numbers?
+    fn matches(&self) -> usize {
+        // Small numbers, like all of those in the puzzle, fit in a bitset.
+        if self.winning_numbers.iter().all(|&n| n < 128) {
+            let winning = self
+                .winning_numbers
+                .iter()
+                .fold(0u128, |set, &n| set | 1 << n);
+
+            self.numbers
+                .iter()
+                .filter(|&&n| n < 128 && winning & 1 << n != 0)
+                .count()
+        } else {
+            self.numbers
+                .iter()
+                .filter(|n| self.winning_numbers.binary_search(n).is_ok())
+                .count()
         }
+    }
 
-        match n_wins {
-            0 => 0,
-            _ => {
-                let mut points = 1;
-                for _ in 1..n_wins {
-                    points *= 2;
-                }
-                points
-            }
+    /// 1 point for the first match, doubled for every match after it.
+    fn points(&self) -> Result<usize> {
+        match self.matches() {
+            0 => Ok(0),
+            n_wins => 1usize.checked_shl(n_wins as u32 - 1).ok_or_else(|| {
+                format!(
+                    "card {} has too many matches ({n_wins}) to count its points",
+                    self.id
+                )
+                .into()
+            }),
         }
     }
 }
 
 const ERR: &str = "invalid input!";
 
+/// Parse a list of numbers, none of which may appear twice.
+fn parse_numbers(s: &str) -> Result<Vec<usize>> {
+    let numbers = s
+        .split_whitespace()
+        .map(|x| x.parse())
+        .collect::<StdResult<Vec<usize>, _>>()?;
+
+    let mut sorted = numbers.clone();
+    sorted.sort_unstable();
+    if let Some(pair) = sorted.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(format!("{ERR} {} appears twice in one list", pair[0]).into());
+    }
+
+    Ok(numbers)
+}
+
 impl FromStr for Card {
     type Err = BoxErr;
 
     fn from_str(s: &str) -> StdResult<Self, Self::Err> {
-        let s = s.split_once(": ").ok_or(ERR)?.1;
+        let (id, s) = s.split_once(": ").ok_or(ERR)?;
+        let id = id.strip_prefix("Card").ok_or(ERR)?.trim().parse()?;
         let (winning_numbers, numbers) = s.split_once(" | ").ok_or(ERR)?;
 
-        let winning_numbers = winning_numbers
-            .split_whitespace()
-            .map(|x| x.parse())
-            .collect::<StdResult<Vec<usize>, _>>()?;
+        let winning_numbers = parse_numbers(winning_numbers)?;
+        let numbers = parse_numbers(numbers)?;
+
+        Ok(Card::new(id, winning_numbers, numbers))
+    }
+}
+
+/// What one card did in the copy cascade of part two.
+#[derive(Debug, PartialEq)]
+struct CascadeStep {
+    id: usize,
+    matches: usize,
+    /// The original and all the copies won by earlier cards.
+    instances: usize,
+    /// The IDs of the cards which every instance of this one won a copy of.
+    won: Range<usize>,
+}
+
+impl fmt::Display for CascadeStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Card {}: {} match(es), {} instance(s)",
+            self.id, self.matches, self.instances
+        )?;
+
+        let copies = if self.instances == 1 {
+            "copy"
+        } else {
+            "copies"
+        };
+
+        match self.won.len() {
+            0 => write!(f, ", wins nothing"),
+            1 => write!(
+                f,
+                ", wins {} {copies} of card {}",
+                self.instances, self.won.start
+            ),
+            _ => write!(
+                f,
+                ", wins {} {copies} each of cards {}-{}",
+                self.instances,
+                self.won.start,
+                self.won.end - 1
+            ),
+        }
+    }
+}
+
+/// The whole pile of scratchcards.
+struct Table {
+    cards: Vec<Card>,
+}
+
+impl Table {
+    fn points(&self) -> Result<usize> {
+        self.cards.iter().try_fold(0usize, |sum, card| {
+            sum.checked_add(card.points()?)
+                .ok_or_else(|| "the scratchcards are worth too many points to count!".into())
+        })
+    }
+
+    /// Play out part two card by card, until all the copies have been won.
+    /// The number of instances grows quickly, so this fails once it's too large to count.
+    fn cascade(&self) -> Result<Vec<CascadeStep>> {
+        let mut instances = vec![1usize; self.cards.len()];
+        let mut steps = Vec::with_capacity(self.cards.len());
+
+        for (i, card) in self.cards.iter().enumerate() {
+            let matches = card.matches();
+            let won = i + 1..(i + 1 + matches).min(self.cards.len());
+
+            for j in won.clone() {
+                instances[j] = instances[j].checked_add(instances[i]).ok_or_else(|| {
+                    format!(
+                        "card {} wins too many copies of card {} to count",
+                        card.id,
+                        j + 1
+                    )
+                })?;
+            }
+
+            steps.push(CascadeStep {
+                id: card.id,
+                matches,
+                instances: instances[i],
+                // The cards are numbered from 1, in order.
+                won: won.start + 1..won.end + 1,
+            });
+        }
+
+        Ok(steps)
+    }
+
+    /// How many instances of each card there are once all the copies have been won.
+    fn instances(&self) -> Result<Vec<usize>> {
+        Ok(self.cascade()?.iter().map(|step| step.instances).collect())
+    }
+
+    fn total_cards(&self) -> Result<usize> {
+        self.instances()?.iter().try_fold(0usize, |sum, &n| {
+            sum.checked_add(n)
+                .ok_or_else(|| "there are too many scratchcards to count!".into())
+        })
+    }
+}
+
+impl FromStr for Table {
+    type Err = BoxErr;
 
-        let numbers = numbers
-            .split_whitespace()
-            .map(|x| x.parse())
-            .collect::<StdResult<Vec<usize>, _>>()?;
+    /// Parses one card per line. The cards have to be numbered from 1 without gaps,
+    /// and all have as many winning numbers and numbers you have as the first one.
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        let cards = s
+            .lines()
+            .map(|l| l.parse())
+            .collect::<Result<Vec<Card>>>()?;
+
+        for (i, card) in cards.iter().enumerate() {
+            if card.id != i + 1 {
+                return Err(
+                    format!("{ERR} expected card {}, found card {}", i + 1, card.id).into(),
+                );
+            }
+            if card.winning_numbers.len() != cards[0].winning_numbers.len()
+                || card.numbers.len() != cards[0].numbers.len()
+            {
+                return Err(
+                    format!("{ERR} card {} has lists of different lengths", card.id).into(),
+                );
+            }
+        }
 
-        Ok(Card::new(winning_numbers, numbers))
+        Ok(Self { cards })
     }
 }
 
+/// Write the matches and instances of every card as CSV.
+fn write_csv(steps: &[CascadeStep], out: &mut impl Write) -> Result<()> {
+    writeln!(out, "card,matches,instances")?;
+
+    for step in steps {
+        writeln!(out, "{},{},{}", step.id, step.matches, step.instances)?;
+    }
+
+    Ok(())
+}
+
 fn main() -> Result<()> {
     let input = fs::read_to_string("input.txt")?;
 
-    let sum: usize = input
-        .lines()
-        .map(|l| l.parse())
-        .collect::<Result<Vec<Card>>>()?
-        .into_iter()
-        .map(|c| c.points())
-        .sum();
+    let table: Table = input.parse()?;
+    let mut stdout = io::stdout().lock();
+
+    // `day04 --trace` shows how the cascade unfolds, `day04 --csv` exports the instance counts.
+    match env::args().nth(1).as_deref() {
+        Some("--trace") => {
+            for step in table.cascade()? {
+                writeln!(stdout, "{step}")?;
+            }
+            return Ok(());
+        }
+        Some("--csv") => return write_csv(&table.cascade()?, &mut stdout),
+        Some(arg) => return Err(format!("unknown argument: {arg}").into()),
+        None => {}
+    }
+
+    let points = table.points()?;
+    let total_cards = table.total_cards()?;
 
-    writeln!(io::stdout(), "The scratchcards are worth {sum} points!")?;
+    writeln!(
+        stdout,
+        "The scratchcards are worth {points} points!\n\
+         You end up with {total_cards} scratchcards in total!"
+    )?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    // Tests are from the puzzle description at the top.
+
+    const EXAMPLE: &str = "\
+Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
+
+    #[test]
+    fn part1() {
+        let table: Table = EXAMPLE.parse().unwrap();
+        let matches: Vec<usize> = table.cards.iter().map(Card::matches).collect();
+
+        assert_eq!(matches, [4, 2, 2, 1, 0, 0]);
+        assert_eq!(table.points().unwrap(), 13);
+    }
+
+    #[test]
+    fn part2() {
+        let table: Table = EXAMPLE.parse().unwrap();
+
+        assert_eq!(table.instances().unwrap(), [1, 2, 4, 8, 14, 1]);
+        assert_eq!(table.total_cards().unwrap(), 30);
+    }
+
+    #[test]
+    fn trace() {
+        let table: Table = EXAMPLE.parse().unwrap();
+        let steps = table.cascade().unwrap();
+
+        assert_eq!(
+            steps[2],
+            CascadeStep {
+                id: 3,
+                matches: 2,
+                instances: 4,
+                won: 4..6
+            }
+        );
+
+        let trace: Vec<String> = steps.iter().map(|s| s.to_string()).collect();
+        assert_eq!(
+            trace,
+            [
+                "Card 1: 4 match(es), 1 instance(s), wins 1 copy each of cards 2-5",
+                "Card 2: 2 match(es), 2 instance(s), wins 2 copies each of cards 3-4",
+                "Card 3: 2 match(es), 4 instance(s), wins 4 copies each of cards 4-5",
+                "Card 4: 1 match(es), 8 instance(s), wins 8 copies of card 5",
+                "Card 5: 0 match(es), 14 instance(s), wins nothing",
+                "Card 6: 0 match(es), 1 instance(s), wins nothing",
+            ]
+        );
+
+        let mut csv = vec![];
+        write_csv(&steps, &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "card,matches,instances\n1,4,1\n2,2,2\n3,2,4\n4,1,8\n5,0,14\n6,0,1\n"
+        );
+    }
+
+    #[test]
+    fn validation() {
+        let card: Card = "Card  12: 1 2 | 3 4".parse().unwrap();
+        assert_eq!(card.id, 12);
+
+        assert!("Card 1: 1 2 1 | 3 4".parse::<Card>().is_err());
+        assert!("Card 1: 1 2 | 3 3".parse::<Card>().is_err());
+        assert!("Card x: 1 2 | 3 4".parse::<Card>().is_err());
+        assert!("Card 1: 1 2 | 3 4\nCard 3: 1 2 | 3 4"
+            .parse::<Table>()
+            .is_err());
+        assert!("Card 1: 1 2 | 3 4\nCard 2: 1 | 3 4"
+            .parse::<Table>()
+            .is_err());
+        assert!("Card 1: 1 2 | 3 4\nCard 2: 1 2 | 3 4 5"
+            .parse::<Table>()
+            .is_err());
+    }
+
+    #[test]
+    fn large_numbers() {
+        let card: Card = "Card 1: 1 200 3000 | 3000 7 200 8".parse().unwrap();
+        assert_eq!(card.matches(), 2);
+
+        let card: Card = "Card 1: 127 128 | 127 128".parse().unwrap();
+        assert_eq!(card.matches(), 2);
+
+        let card: Card = "Card 1: 1 127 | 127 128".parse().unwrap();
+        assert_eq!(card.matches(), 1);
+    }
+
+    #[test]
+    fn points_overflow() {
+        let card =
+            |n_matches: usize| Card::new(1, (0..n_matches).collect(), (0..n_matches).collect());
+
+        assert_eq!(card(64).points().unwrap(), 1 << 63);
+
+        let err = card(65).points().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "card 1 has too many matches (65) to count its points"
+        );
+
+        let table = Table {
+            cards: vec![card(64), card(64)],
+        };
+        assert!(table.points().is_err());
+    }
+
+    #[test]
+    fn cascade_overflow() {
+        // Every card wins a copy of each of the next ten, so the instances
+        // grow a little slower than doubling from card to card.
+        let card = |id: usize| Card::new(id, (0..10).collect(), (0..10).collect());
+        let table = Table {
+            cards: (1..=300).map(card).collect(),
+        };
+
+        let err = table.cascade().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "card 65 wins too many copies of card 66 to count"
+        );
+        assert!(table.total_cards().is_err());
+
+        let table = Table {
+            cards: (1..=60).map(card).collect(),
+        };
+        assert!(table.total_cards().is_ok());
+    }
+
+    /// Counting matches with `contains`, as `Card::matches` did before the bitset.
+    fn matches_contains(card: &Card) -> usize {
+        card.numbers
+            .iter()
+            .filter(|num| card.winning_numbers.contains(num))
+            .count()
+    }
+
+    /// A table of `n_cards` cards with `n_winning` winning numbers
+    /// and `n_numbers` numbers you have, all below `max`.
+    ///
+    /// Each list steps through the numbers by a stride coprime to `max`, so it has
+    /// no duplicates, and starts at a different place for every card and list,
+    /// so that the cards have anything from no matches to a full house.
+    fn generate_table(n_cards: usize, n_winning: usize, n_numbers: usize, max: usize) -> Table {
+        const STRIDE: usize = 7919;
+        let draw = |start: usize, n: usize| (0..n).map(|k| (start + k * STRIDE) % max).collect();
+
+        let cards = (1..=n_cards)
+            .map(|id| Card::new(id, draw(id * 13, n_winning), draw(id * 29 + 3, n_numbers)))
+            .collect();
+
+        Table { cards }
+    }
+
+    /// Times `Card::matches` against the quadratic scan on thousands of cards.
+    /// Debug builds say little about this; use `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench() {
+        // The puzzle's shape of cards, and longer lists of larger numbers.
+        for (n_cards, n_winning, n_numbers, max) in
+            [(200_000, 10, 25, 100), (20_000, 100, 250, 100_000)]
+        {
+            let table = generate_table(n_cards, n_winning, n_numbers, max);
+
+            let start = Instant::now();
+            let expected: usize = table.cards.iter().map(matches_contains).sum();
+            let contains_time = start.elapsed();
+
+            let start = Instant::now();
+            let n_matches: usize = table.cards.iter().map(Card::matches).sum();
+            let time = start.elapsed();
+
+            println!(
+                "{n_cards} cards, {n_winning} | {n_numbers} numbers below {max}: \
+                 {contains_time:?} (contains) -> {time:?}"
+            );
+
+            assert_eq!(n_matches, expected);
+        }
+    }
+}
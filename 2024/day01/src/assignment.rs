@@ -0,0 +1,71 @@
+//! The assignment problem, solved with the Hungarian algorithm.
+
+/// The lowest total cost of pairing every row with a different column
+/// of the square matrix `cost`, where `cost(i, j)` is the cost of row `i` and column `j`.
+///
+/// Runs in `O(n^3)` time.
+pub fn min_cost(n: usize, cost: impl Fn(usize, usize) -> i64) -> i64 {
+    // Potentials for rows (`u`) and columns (`v`), and the row matched to each column (`p`),
+    // all 1-based so that index 0 can stand for the row being added.
+    let mut u = vec![0i64; n + 1];
+    let mut v = vec![0i64; n + 1];
+    let mut p = vec![0usize; n + 1];
+    let mut way = vec![0usize; n + 1];
+
+    for i in 1..=n {
+        p[0] = i;
+        let mut j0 = 0;
+        let mut min_v = vec![i64::MAX; n + 1];
+        let mut used = vec![false; n + 1];
+
+        // Grow an alternating path from row `i` until it reaches a free column.
+        loop {
+            used[j0] = true;
+            let i0 = p[j0];
+            let mut delta = i64::MAX;
+            let mut j1 = 0;
+
+            for j in 1..=n {
+                if used[j] {
+                    continue;
+                }
+
+                let reduced = cost(i0 - 1, j - 1) - u[i0] - v[j];
+                if reduced < min_v[j] {
+                    min_v[j] = reduced;
+                    way[j] = j0;
+                }
+                if min_v[j] < delta {
+                    delta = min_v[j];
+                    j1 = j;
+                }
+            }
+
+            for j in 0..=n {
+                if used[j] {
+                    u[p[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_v[j] -= delta;
+                }
+            }
+
+            j0 = j1;
+            if p[j0] == 0 {
+                break;
+            }
+        }
+
+        // Flip the matching along the path.
+        loop {
+            let j1 = way[j0];
+            p[j0] = p[j1];
+            j0 = j1;
+            if j0 == 0 {
+                break;
+            }
+        }
+    }
+
+    (1..=n).map(|j| cost(p[j] - 1, j - 1)).sum()
+}
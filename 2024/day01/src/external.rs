@@ -0,0 +1,241 @@
+//! Total distance and similarity score for lists too big to hold in memory.
+//!
+//! Each list is read in runs of at most `max_run` IDs. Full runs are sorted and
+//! spilled to disk, so only the current run of each list is ever held in memory.
+//! Both answers are then computed by merging the sorted runs of the two lists,
+//! after merging runs into longer ones until there are few enough to open at once.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{self, AtomicUsize};
+
+use crate::{parse_line, Result};
+
+/// The most run files of one list which are read at the same time.
+const FAN_IN: usize = 64;
+
+/// Distinguishes the run files of different lists within the process.
+static NEXT_FILE: AtomicUsize = AtomicUsize::new(0);
+
+/// One list, as sorted runs on disk plus the run still being read.
+struct SortedRuns<'a> {
+    dir: &'a Path,
+    files: Vec<PathBuf>,
+    current: Vec<usize>,
+}
+
+impl<'a> SortedRuns<'a> {
+    fn new(dir: &'a Path) -> Self {
+        Self {
+            dir,
+            files: vec![],
+            current: vec![],
+        }
+    }
+
+    fn push(&mut self, id: usize, max_run: usize) -> Result<()> {
+        self.current.push(id);
+        if self.current.len() >= max_run {
+            self.spill()?;
+        }
+
+        Ok(())
+    }
+
+    fn spill(&mut self) -> Result<()> {
+        self.current.sort_unstable();
+        let ids = self.current.iter().map(|&id| Ok(id));
+        let path = self.write_run(ids)?;
+        self.files.push(path);
+
+        self.current.clear();
+        Ok(())
+    }
+
+    /// Write sorted IDs to a new run file.
+    fn write_run(&self, ids: impl Iterator<Item = io::Result<usize>>) -> Result<PathBuf> {
+        let n = NEXT_FILE.fetch_add(1, atomic::Ordering::Relaxed);
+        let path = self.dir.join(format!("day01-{}-{n}.run", process::id()));
+
+        let result = File::create(&path).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            for id in ids {
+                out.write_all(&(id? as u64).to_le_bytes())?;
+            }
+            out.flush()
+        });
+
+        match result {
+            Ok(()) => Ok(path),
+            Err(e) => {
+                let _ = fs::remove_file(&path);
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Merge the oldest runs into one until there are at most `FAN_IN` left.
+    fn compact(&mut self) -> Result<()> {
+        while self.files.len() > FAN_IN {
+            let mut runs: Vec<Box<dyn Iterator<Item = io::Result<usize>>>> = vec![];
+            for path in &self.files[..FAN_IN] {
+                runs.push(Box::new(RunReader(BufReader::new(File::open(path)?))));
+            }
+
+            let path = self.write_run(Merge::new(runs)?)?;
+            for old in self.files.drain(..FAN_IN) {
+                fs::remove_file(old)?;
+            }
+            self.files.push(path);
+        }
+
+        Ok(())
+    }
+
+    /// All IDs of the list in ascending order.
+    fn merged(&mut self) -> Result<Merge<'_>> {
+        self.compact()?;
+        self.current.sort_unstable();
+
+        let mut runs: Vec<Box<dyn Iterator<Item = io::Result<usize>> + '_>> =
+            vec![Box::new(self.current.iter().map(|&id| Ok(id)))];
+        for path in &self.files {
+            runs.push(Box::new(RunReader(BufReader::new(File::open(path)?))));
+        }
+
+        Merge::new(runs)
+    }
+}
+
+impl Drop for SortedRuns<'_> {
+    fn drop(&mut self) {
+        for path in &self.files {
+            // There is nothing else to do about a run which cannot be removed.
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// The IDs in a run file.
+struct RunReader(BufReader<File>);
+
+impl Iterator for RunReader {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut bytes = [0; 8];
+
+        match self.0.read_exact(&mut bytes) {
+            Ok(()) => Some(Ok(u64::from_le_bytes(bytes) as usize)),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// A k-way merge of sorted runs.
+struct Merge<'a> {
+    runs: Vec<Box<dyn Iterator<Item = io::Result<usize>> + 'a>>,
+    /// The smallest unmerged ID of every run which has one left, with the run's index.
+    heads: BinaryHeap<Reverse<(usize, usize)>>,
+}
+
+impl<'a> Merge<'a> {
+    fn new(mut runs: Vec<Box<dyn Iterator<Item = io::Result<usize>> + 'a>>) -> Result<Self> {
+        let mut heads = BinaryHeap::new();
+
+        for (i, run) in runs.iter_mut().enumerate() {
+            if let Some(id) = run.next() {
+                heads.push(Reverse((id?, i)));
+            }
+        }
+
+        Ok(Self { runs, heads })
+    }
+}
+
+impl Iterator for Merge<'_> {
+    type Item = io::Result<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((id, i)) = self.heads.pop()?;
+
+        match self.runs[i].next() {
+            Some(Ok(next)) => self.heads.push(Reverse((next, i))),
+            Some(Err(e)) => return Some(Err(e)),
+            None => {}
+        }
+
+        Some(Ok(id))
+    }
+}
+
+/// The next distinct ID of a sorted list and how many times it occurs.
+fn next_group(ids: &mut Peekable<Merge>) -> Result<Option<(usize, usize)>> {
+    let Some(id) = ids.next().transpose()? else {
+        return Ok(None);
+    };
+
+    let mut count = 1;
+    while let Some(Ok(next)) = ids.peek() {
+        if *next != id {
+            break;
+        }
+        count += 1;
+        ids.next();
+    }
+
+    Ok(Some((id, count)))
+}
+
+/// The total distance and the similarity score of the lists in `reader`,
+/// holding at most `max_run` IDs of each list in memory and spilling the rest to `dir`.
+pub fn stream_stats(
+    mut reader: impl BufRead,
+    max_run: usize,
+    dir: &Path,
+) -> Result<(usize, usize)> {
+    let max_run = max_run.max(1);
+    let mut left = SortedRuns::new(dir);
+    let mut rght = SortedRuns::new(dir);
+    let mut line = String::new();
+
+    while reader.read_line(&mut line)? > 0 {
+        let (l, r) = parse_line(&line)?;
+        left.push(l, max_run)?;
+        rght.push(r, max_run)?;
+        line.clear();
+    }
+
+    // First pass: pair up the IDs in sorted order.
+    let mut dist = 0;
+    for (l, r) in left.merged()?.zip(rght.merged()?) {
+        dist += l?.abs_diff(r?);
+    }
+
+    // Second pass: walk both lists in step, one distinct ID at a time.
+    let mut similarity = 0;
+    let mut lefts = left.merged()?.peekable();
+    let mut rghts = rght.merged()?.peekable();
+    let mut l = next_group(&mut lefts)?;
+    let mut r = next_group(&mut rghts)?;
+
+    while let (Some((l_id, n_left)), Some((r_id, n_rght))) = (l, r) {
+        match l_id.cmp(&r_id) {
+            Ordering::Less => l = next_group(&mut lefts)?,
+            Ordering::Greater => r = next_group(&mut rghts)?,
+            Ordering::Equal => {
+                similarity += l_id * n_left * n_rght;
+                l = next_group(&mut lefts)?;
+                r = next_group(&mut rghts)?;
+            }
+        }
+    }
+
+    Ok((dist, similarity))
+}
@@ -101,9 +101,15 @@ So, for these example lists, the similarity score at the end of this process is
 Once again consider your left and right lists. What is their similarity score?
 */
 
+mod assignment;
+mod external;
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
 use std::error::Error;
-use std::fs;
-use std::io::{self, Write};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Write};
 use std::result::Result as StdResult;
 use std::str::FromStr;
 
@@ -111,6 +117,146 @@ type Result<T> = StdResult<T, Box<dyn Error>>;
 
 const ERR: &str = "invalid input!";
 
+/// The cost of pairing a left ID with a right ID.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cost {
+    Abs,
+    Squared,
+    /// Zero for equal IDs, one otherwise.
+    Mismatch,
+    /// The absolute difference, but never more than the given cap.
+    Capped(usize),
+}
+
+impl Cost {
+    /// `None` if the cost doesn't fit in a `usize`.
+    fn between(self, l: usize, r: usize) -> Option<usize> {
+        let diff = l.abs_diff(r);
+
+        match self {
+            Cost::Abs => Some(diff),
+            Cost::Squared => diff.checked_mul(diff),
+            Cost::Mismatch => Some(usize::from(diff != 0)),
+            Cost::Capped(cap) => Some(diff.min(cap)),
+        }
+    }
+}
+
+impl FromStr for Cost {
+    type Err = &'static str;
+
+    /// Parses `abs`, `squared`, `mismatch` or `capped:K`.
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        match s {
+            "abs" => Ok(Cost::Abs),
+            "squared" => Ok(Cost::Squared),
+            "mismatch" => Ok(Cost::Mismatch),
+            _ => {
+                let cap = s.strip_prefix("capped:").ok_or(ERR)?;
+                Ok(Cost::Capped(cap.parse().map_err(|_| ERR)?))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Cost {
+    /// Writes the cost the way `from_str` reads it.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Cost::Abs => write!(f, "abs"),
+            Cost::Squared => write!(f, "squared"),
+            Cost::Mismatch => write!(f, "mismatch"),
+            Cost::Capped(cap) => write!(f, "capped:{cap}"),
+        }
+    }
+}
+
+/// A way of measuring how far apart the two lists are.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Metric {
+    /// Pair the smallest IDs, then the second smallest, and so on (part 1).
+    Sorted,
+    /// Pair the IDs line by line, as they appear in the input.
+    Original,
+    /// The number of IDs left over after pairing off every ID with an equal one on the other side.
+    SymmetricDifference,
+    /// The cheapest possible one-to-one pairing under the given cost.
+    Assignment(Cost),
+}
+
+impl FromStr for Metric {
+    type Err = &'static str;
+
+    /// Parses `sorted`, `original`, `symdiff` or `assign:COST`.
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        match s {
+            "sorted" => Ok(Metric::Sorted),
+            "original" => Ok(Metric::Original),
+            "symdiff" => Ok(Metric::SymmetricDifference),
+            _ => {
+                let cost = s.strip_prefix("assign:").ok_or(ERR)?;
+                Ok(Metric::Assignment(cost.parse()?))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    /// Writes the metric the way `from_str` reads it.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Metric::Sorted => write!(f, "sorted"),
+            Metric::Original => write!(f, "original"),
+            Metric::SymmetricDifference => write!(f, "symdiff"),
+            Metric::Assignment(cost) => write!(f, "assign:{cost}"),
+        }
+    }
+}
+
+/// How one location ID shows up in the two lists.
+#[derive(Debug, PartialEq)]
+struct Reconciliation {
+    id: usize,
+    left: usize,
+    rght: usize,
+}
+
+impl Reconciliation {
+    /// What the ID adds to the similarity score.
+    fn similarity(&self) -> usize {
+        self.id * self.left * self.rght
+    }
+
+    /// `"left"` or `"right"` if only that list has the ID.
+    fn unique_to(&self) -> Option<&'static str> {
+        match (self.left, self.rght) {
+            (_, 0) => Some("left"),
+            (0, _) => Some("right"),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Reconciliation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} left, {} right, similarity {}",
+            self.id,
+            self.left,
+            self.rght,
+            self.similarity()
+        )?;
+
+        if let Some(side) = self.unique_to() {
+            write!(f, " (only {side})")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Both lists, in the order they were written down.
 struct LocIds {
     left: Vec<usize>,
     rght: Vec<usize>,
@@ -121,27 +267,115 @@ impl LocIds {
         Self { left, rght }
     }
 
-    fn total_dist(&self) -> usize {
-        self.left.iter().enumerate().fold(0, |mut acc, (i, l)| {
-            let r = self.rght[i];
-            acc += l.abs_diff(r);
-            acc
+    fn distance(&self, metric: Metric) -> StdResult<usize, &'static str> {
+        Ok(match metric {
+            Metric::Sorted => self.total_dist(),
+            Metric::Original => self
+                .left
+                .iter()
+                .zip(&self.rght)
+                .map(|(l, r)| l.abs_diff(*r))
+                .sum(),
+            Metric::SymmetricDifference => self.sym_diff(),
+            Metric::Assignment(cost) => self.optimal_assignment(cost)?,
         })
     }
 
-    fn occurrences_right(&self, n: usize) -> usize {
-        self.rght.iter().filter(|x| **x == n).count()
+    fn total_dist(&self) -> usize {
+        let mut left = self.left.clone();
+        let mut rght = self.rght.clone();
+        left.sort_unstable();
+        rght.sort_unstable();
+
+        left.iter().zip(&rght).map(|(l, r)| l.abs_diff(*r)).sum()
+    }
+
+    fn sym_diff(&self) -> usize {
+        let mut balance: HashMap<usize, isize> = HashMap::new();
+
+        for &l in &self.left {
+            *balance.entry(l).or_insert(0) += 1;
+        }
+        for &r in &self.rght {
+            *balance.entry(r).or_insert(0) -= 1;
+        }
+
+        balance.values().map(|b| b.unsigned_abs()).sum()
     }
 
-    fn sim_score(&mut self) -> usize {
-        self.left.dedup();
-        self.left.iter().fold(0, |mut acc, l| {
-            acc += l * self.occurrences_right(*l);
-            acc
-        })
+    /// The lowest total cost of pairing every left ID with a different right ID.
+    /// This takes cubic time in the length of the lists.
+    ///
+    /// Fails if a cost is too big for the potentials and the total to fit in an `i64`.
+    fn optimal_assignment(&self, cost: Cost) -> StdResult<usize, &'static str> {
+        let n = self.left.len();
+        let max_cost = i64::MAX / (2 * n as i64 + 2);
+
+        let mut costs = Vec::with_capacity(n * n);
+        for &l in &self.left {
+            for &r in &self.rght {
+                let c = cost.between(l, r).ok_or(ERR)?;
+                let c = i64::try_from(c).map_err(|_| ERR)?;
+                if c > max_cost {
+                    return Err(ERR);
+                }
+                costs.push(c);
+            }
+        }
+
+        let total = assignment::min_cost(n, |i, j| costs[i * n + j]);
+        usize::try_from(total).map_err(|_| ERR)
+    }
+
+    fn occurrences_right(&self) -> HashMap<usize, usize> {
+        let mut occurrences = HashMap::new();
+
+        for &r in &self.rght {
+            *occurrences.entry(r).or_insert(0) += 1;
+        }
+
+        occurrences
+    }
+
+    /// Every distinct ID in either list, in ascending order.
+    fn reconcile(&self) -> Vec<Reconciliation> {
+        let mut counts: BTreeMap<usize, (usize, usize)> = BTreeMap::new();
+
+        for &l in &self.left {
+            counts.entry(l).or_default().0 += 1;
+        }
+        for &r in &self.rght {
+            counts.entry(r).or_default().1 += 1;
+        }
+
+        counts
+            .into_iter()
+            .map(|(id, (left, rght))| Reconciliation { id, left, rght })
+            .collect()
+    }
+
+    fn sim_score(&self) -> usize {
+        let occurrences = self.occurrences_right();
+
+        self.left
+            .iter()
+            .map(|l| l * occurrences.get(l).copied().unwrap_or(0))
+            .sum()
     }
 }
 
+/// The left and right ID on a line of the input.
+fn parse_line(l: &str) -> StdResult<(usize, usize), &'static str> {
+    let mut spl = l.split_whitespace();
+    let id_left = spl.next().ok_or(ERR)?;
+    let id_rght = spl.next().ok_or(ERR)?;
+
+    Ok((
+        id_left.parse().map_err(|_| ERR)?,
+        id_rght.parse().map_err(|_| ERR)?,
+    ))
+}
+
 impl FromStr for LocIds {
     type Err = &'static str;
 
@@ -150,32 +384,296 @@ impl FromStr for LocIds {
         let mut rght = vec![];
 
         for l in s.lines() {
-            let mut spl = l.split_whitespace();
-            let id_left = spl.next().ok_or(ERR)?;
-            let id_rght = spl.next().ok_or(ERR)?;
-
-            left.push(id_left.parse().map_err(|_| ERR)?);
-            rght.push(id_rght.parse().map_err(|_| ERR)?);
+            let (id_left, id_rght) = parse_line(l)?;
+            left.push(id_left);
+            rght.push(id_rght);
         }
 
-        left.sort_unstable();
-        rght.sort_unstable();
-
         Ok(LocIds::new(left, rght))
     }
 }
 
+fn write_csv(report: &[Reconciliation], out: &mut impl Write) -> Result<()> {
+    writeln!(out, "id,left,right,similarity,unique_to")?;
+
+    for r in report {
+        writeln!(
+            out,
+            "{},{},{},{},{}",
+            r.id,
+            r.left,
+            r.rght,
+            r.similarity(),
+            r.unique_to().unwrap_or("")
+        )?;
+    }
+
+    Ok(())
+}
+
+/// How many IDs of each list `day01 --stream` holds in memory by default.
+const MAX_RUN: usize = 1 << 20;
+
 fn main() -> Result<()> {
+    let mut stdout = io::stdout().lock();
+
+    // `day01 --stream [MAX_RUN]` gives the same answers without reading all of the input into memory.
+    let mut args = env::args().skip(1);
+    if let Some("--stream") = args.next().as_deref() {
+        let max_run = match args.next() {
+            Some(n) => n.parse()?,
+            None => MAX_RUN,
+        };
+        let input = BufReader::new(File::open("input.txt")?);
+        let (dist, sim_score) = external::stream_stats(input, max_run, &env::temp_dir())?;
+
+        writeln!(
+            stdout,
+            "The total distance is: {dist}\nThe similarity score is: {sim_score}"
+        )?;
+        return Ok(());
+    }
+
     let input = fs::read_to_string("input.txt")?;
+    let locids: LocIds = input.parse()?;
+
+    // `day01 --report` and `day01 --csv` list how every ID shows up in the two lists,
+    // `day01 METRIC` compares the lists with another metric, e.g. `original` or `assign:squared`.
+    match env::args().nth(1).as_deref() {
+        Some("--report") => {
+            for r in locids.reconcile() {
+                writeln!(stdout, "{r}")?;
+            }
+            return Ok(());
+        }
+        Some("--csv") => return write_csv(&locids.reconcile(), &mut stdout),
+        Some(metric) => {
+            let metric: Metric = metric.parse()?;
+            let dist = locids.distance(metric)?;
+            writeln!(stdout, "The {metric} distance is: {dist}")?;
+            return Ok(());
+        }
+        None => {}
+    }
 
-    let mut locids: LocIds = input.parse()?;
     let dist = locids.total_dist();
     let sim_score = locids.sim_score();
 
     writeln!(
-        io::stdout(),
+        stdout,
         "The total distance is: {dist}\nThe similarity score is: {sim_score}"
     )?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tests are from the puzzle description at the top.
+
+    const EXAMPLE: &str = "\
+3   4
+4   3
+2   5
+1   3
+3   9
+3   3";
+
+    #[test]
+    fn part1() {
+        let locids: LocIds = EXAMPLE.parse().unwrap();
+        assert_eq!(locids.total_dist(), 11);
+    }
+
+    #[test]
+    fn part2() {
+        let locids: LocIds = EXAMPLE.parse().unwrap();
+        assert_eq!(locids.sim_score(), 31);
+    }
+
+    #[test]
+    fn sim_score_counts_repeated_left_ids() {
+        let locids = LocIds::new(vec![3, 3], vec![3]);
+        assert_eq!(locids.sim_score(), 6);
+    }
+
+    #[test]
+    fn metrics() {
+        let locids: LocIds = EXAMPLE.parse().unwrap();
+
+        // 1 + 1 + 3 + 2 + 6 + 0
+        assert_eq!(locids.distance(Metric::Original).unwrap(), 13);
+        // 1, 2, 5 and 9 have no partner.
+        assert_eq!(locids.distance(Metric::SymmetricDifference).unwrap(), 4);
+        assert_eq!(locids.distance(Metric::Assignment(Cost::Abs)).unwrap(), 11);
+        assert_eq!(
+            locids.distance(Metric::Assignment(Cost::Mismatch)).unwrap(),
+            2
+        );
+        assert_eq!(
+            locids
+                .distance(Metric::Assignment(Cost::Capped(1)))
+                .unwrap(),
+            2
+        );
+    }
+
+    #[test]
+    fn parse_metric() {
+        assert_eq!("sorted".parse(), Ok(Metric::Sorted));
+        assert_eq!("symdiff".parse(), Ok(Metric::SymmetricDifference));
+        assert_eq!(
+            "assign:capped:3".parse(),
+            Ok(Metric::Assignment(Cost::Capped(3)))
+        );
+        for metric in [
+            "sorted",
+            "original",
+            "symdiff",
+            "assign:abs",
+            "assign:capped:3",
+        ] {
+            assert_eq!(metric.parse::<Metric>().unwrap().to_string(), metric);
+        }
+        assert!("assign:cubed".parse::<Metric>().is_err());
+        assert!("closest".parse::<Metric>().is_err());
+    }
+
+    #[test]
+    fn reconcile() {
+        let locids: LocIds = EXAMPLE.parse().unwrap();
+        let report = locids.reconcile();
+
+        let ids: Vec<usize> = report.iter().map(|r| r.id).collect();
+        assert_eq!(ids, [1, 2, 3, 4, 5, 9]);
+        assert_eq!(
+            report[2],
+            Reconciliation {
+                id: 3,
+                left: 3,
+                rght: 3
+            }
+        );
+        assert_eq!(report[0].unique_to(), Some("left"));
+        assert_eq!(report[3].unique_to(), None);
+        assert_eq!(report[5].unique_to(), Some("right"));
+        assert_eq!(
+            report.iter().map(Reconciliation::similarity).sum::<usize>(),
+            locids.sim_score()
+        );
+
+        let mut csv = vec![];
+        write_csv(&report, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(
+            csv.lines().take(3).collect::<Vec<_>>(),
+            [
+                "id,left,right,similarity,unique_to",
+                "1,1,0,0,left",
+                "2,1,0,0,left"
+            ]
+        );
+        assert_eq!(report[2].to_string(), "3: 3 left, 3 right, similarity 27");
+        assert_eq!(
+            report[5].to_string(),
+            "9: 0 left, 1 right, similarity 0 (only right)"
+        );
+    }
+
+    /// `n` IDs below `max`, from a linear congruential generator started at `seed`.
+    fn random_ids(mut seed: u64, n: usize, max: usize) -> Vec<usize> {
+        (0..n)
+            .map(|_| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                (seed >> 33) as usize % max
+            })
+            .collect()
+    }
+
+    #[test]
+    fn streaming() {
+        let dir = env::temp_dir().join(format!("day01-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let (dist, sim_score) = external::stream_stats(EXAMPLE.as_bytes(), 2, &dir).unwrap();
+        assert_eq!((dist, sim_score), (11, 31));
+
+        let left = random_ids(3, 10_000, 500);
+        let rght = random_ids(4, 10_000, 500);
+        let input: String = left
+            .iter()
+            .zip(&rght)
+            .map(|(l, r)| format!("{l}   {r}\n"))
+            .collect();
+        let locids: LocIds = input.parse().unwrap();
+
+        for max_run in [1, 7, 1000, 20_000] {
+            assert_eq!(
+                external::stream_stats(input.as_bytes(), max_run, &dir).unwrap(),
+                (locids.total_dist(), locids.sim_score()),
+                "max_run {max_run}"
+            );
+        }
+
+        assert!(external::stream_stats("1 2\nthree 4\n".as_bytes(), 1, &dir).is_err());
+
+        // The runs are cleaned up, even after an error.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    /// Every way of pairing up the lists, by brute force.
+    fn brute_force(locids: &LocIds, cost: Cost) -> usize {
+        fn go(locids: &LocIds, cost: Cost, i: usize, used: &mut [bool]) -> usize {
+            if i == locids.left.len() {
+                return 0;
+            }
+
+            let mut best = usize::MAX;
+            for j in 0..used.len() {
+                if !used[j] {
+                    used[j] = true;
+                    let c = cost.between(locids.left[i], locids.rght[j]).unwrap()
+                        + go(locids, cost, i + 1, used);
+                    used[j] = false;
+                    best = best.min(c);
+                }
+            }
+
+            best
+        }
+
+        go(locids, cost, 0, &mut vec![false; locids.rght.len()])
+    }
+
+    #[test]
+    fn assignment_is_optimal() {
+        for n in 1..=6 {
+            let seed = 2 * n as u64;
+            let locids = LocIds::new(random_ids(seed, n, 20), random_ids(seed + 1, n, 20));
+
+            for cost in [Cost::Abs, Cost::Squared, Cost::Mismatch, Cost::Capped(4)] {
+                assert_eq!(
+                    locids.optimal_assignment(cost).unwrap(),
+                    brute_force(&locids, cost),
+                    "{cost:?} on {:?} / {:?}",
+                    locids.left,
+                    locids.rght
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn huge_assignment_costs() {
+        let locids = LocIds::new(vec![0, 1], vec![1 << 33, 1]);
+        assert_eq!(locids.optimal_assignment(Cost::Squared), Err(ERR));
+        assert_eq!(locids.optimal_assignment(Cost::Abs).unwrap(), 1 << 33);
+
+        let locids = LocIds::new(vec![0, 1], vec![usize::MAX, 1]);
+        assert_eq!(locids.optimal_assignment(Cost::Abs), Err(ERR));
+        assert_eq!(locids.optimal_assignment(Cost::Capped(5)).unwrap(), 5);
+    }
+}